@@ -3,9 +3,16 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use marketplace::msg::{CountResponse, ExecuteMsg, InstantiateMsg, QueryMsg, HandleMsg, InitMsg, BuyNft, SellNft};
-use marketplace::state::State;
-use marketplace::package::{ContractInfoResponse, QueryOfferingResult, OfferingResponse };
+use marketplace::msg::{
+    CountResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, HandleMsg, InitMsg, BuyNft, BuyMany, BuySigned, RedeemVoucher, CreateSwap, ListRental, SellNft, SellDutch, ListNft, ReceiveMsg,
+    ReceiveNftMsg,
+};
+use marketplace::state::{Config, State};
+use marketplace::package::{
+    AllowedCollectionsResponse, AllowedCurrenciesResponse, AuctionsResponse, BestCollectionOfferResponse, BundlesResponse, CollectionStatsResponse, ContractInfoResponse, FloorPriceResponse, LazyMintCreatorResponse, NonceUsedResponse, OfferingByTokenResponse, OffersResponse,
+    QueryAuctionResult, QueryBundleResult, QueryCollectionOfferResult, QueryCollectionStatsResult, QueryOfferResult, QueryOfferingResult,
+    QueryRentalResult, QuerySwapResult, QueryTradeResult, OfferingResponse, OfferingsByPriceResponse, RentalsResponse, SwapsResponse, TradesResponse, VoucherRedeemedResponse,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -17,13 +24,48 @@ fn main() {
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
+    export_schema(&schema_for!(Config), &out_dir);
     export_schema(&schema_for!(CountResponse), &out_dir);
     export_schema(&schema_for!(InitMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(HandleMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(SellNft), &out_dir);
+    export_schema(&schema_for!(SellDutch), &out_dir);
+    export_schema(&schema_for!(ListNft), &out_dir);
     export_schema(&schema_for!(BuyNft), &out_dir);
+    export_schema(&schema_for!(BuyMany), &out_dir);
+    export_schema(&schema_for!(BuySigned), &out_dir);
+    export_schema(&schema_for!(RedeemVoucher), &out_dir);
+    export_schema(&schema_for!(CreateSwap), &out_dir);
+    export_schema(&schema_for!(ListRental), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
+    export_schema(&schema_for!(ReceiveNftMsg), &out_dir);
     export_schema(&schema_for!(OfferingResponse), &out_dir);
+    export_schema(&schema_for!(OfferingsByPriceResponse), &out_dir);
     export_schema(&schema_for!(ContractInfoResponse), &out_dir);
     export_schema(&schema_for!(QueryOfferingResult), &out_dir);
+    export_schema(&schema_for!(FloorPriceResponse), &out_dir);
+    export_schema(&schema_for!(OfferingByTokenResponse), &out_dir);
+    export_schema(&schema_for!(NonceUsedResponse), &out_dir);
+    export_schema(&schema_for!(LazyMintCreatorResponse), &out_dir);
+    export_schema(&schema_for!(VoucherRedeemedResponse), &out_dir);
+    export_schema(&schema_for!(QueryAuctionResult), &out_dir);
+    export_schema(&schema_for!(AuctionsResponse), &out_dir);
+    export_schema(&schema_for!(QueryBundleResult), &out_dir);
+    export_schema(&schema_for!(BundlesResponse), &out_dir);
+    export_schema(&schema_for!(QueryRentalResult), &out_dir);
+    export_schema(&schema_for!(RentalsResponse), &out_dir);
+    export_schema(&schema_for!(QuerySwapResult), &out_dir);
+    export_schema(&schema_for!(SwapsResponse), &out_dir);
+    export_schema(&schema_for!(QueryTradeResult), &out_dir);
+    export_schema(&schema_for!(TradesResponse), &out_dir);
+    export_schema(&schema_for!(QueryCollectionStatsResult), &out_dir);
+    export_schema(&schema_for!(CollectionStatsResponse), &out_dir);
+    export_schema(&schema_for!(AllowedCollectionsResponse), &out_dir);
+    export_schema(&schema_for!(AllowedCurrenciesResponse), &out_dir);
+    export_schema(&schema_for!(QueryOfferResult), &out_dir);
+    export_schema(&schema_for!(OffersResponse), &out_dir);
+    export_schema(&schema_for!(QueryCollectionOfferResult), &out_dir);
+    export_schema(&schema_for!(BestCollectionOfferResponse), &out_dir);
 }
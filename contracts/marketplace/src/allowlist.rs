@@ -0,0 +1,242 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Empty, MessageInfo, Order, Response, StdResult};
+use cw_storage_plus::Bound;
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::package::{AllowedCollectionsResponse, AllowedCurrenciesResponse, Price};
+use crate::state::{ALLOWED_COLLECTIONS, ALLOWED_CURRENCIES, CONFIG};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Add and remove allow-listed collections and currencies, only the admin is allowed to call it.
+ * Removals are applied after additions
+ */
+pub fn try_update_allow_list(
+    deps: DepsMut,
+    info: MessageInfo,
+    add_collections: Vec<String>,
+    remove_collections: Vec<String>,
+    add_currencies: Vec<String>,
+    remove_currencies: Vec<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for collection in &add_collections {
+        let collection = deps.api.addr_validate(collection)?;
+        ALLOWED_COLLECTIONS.save(deps.storage, &collection, &Empty {})?;
+    }
+    for collection in &remove_collections {
+        let collection = deps.api.addr_validate(collection)?;
+        ALLOWED_COLLECTIONS.remove(deps.storage, &collection);
+    }
+    for currency in &add_currencies {
+        ALLOWED_CURRENCIES.save(deps.storage, currency, &Empty {})?;
+    }
+    for currency in &remove_currencies {
+        ALLOWED_CURRENCIES.remove(deps.storage, currency);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_allow_list")
+        .add_attribute("add_collections", add_collections.join(","))
+        .add_attribute("remove_collections", remove_collections.join(","))
+        .add_attribute("add_currencies", add_currencies.join(","))
+        .add_attribute("remove_currencies", remove_currencies.join(","))
+    )
+}
+
+/**
+ * Reject a listing of a collection or in a currency that is not allow-listed, unless the
+ * marketplace is in open mode
+ */
+pub(crate) fn check_allowed(deps: Deps, contract_addr: &Addr, price: &Price) -> Result<(), ContractError> {
+    if CONFIG.load(deps.storage)?.open_mode {
+        return Ok(());
+    }
+    if !ALLOWED_COLLECTIONS.has(deps.storage, contract_addr) {
+        return Err(ContractError::CollectionNotAllowed {
+            contract_addr: contract_addr.to_string(),
+        });
+    }
+    let currency = price.currency();
+    if !ALLOWED_CURRENCIES.has(deps.storage, &currency) {
+        return Err(ContractError::CurrencyNotAllowed { currency });
+    }
+    Ok(())
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_allowed_collections(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowedCollectionsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_addr = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start = start_addr.as_ref().map(Bound::exclusive);
+
+    let collections = ALLOWED_COLLECTIONS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    Ok(AllowedCollectionsResponse { collections })
+}
+
+pub fn query_allowed_currencies(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowedCurrenciesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let currencies = ALLOWED_CURRENCIES
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    Ok(AllowedCurrenciesResponse { currencies })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, sell_nft};
+    use crate::contract::{execute, instantiate, query};
+    use crate::msg::{AcceptOffer, HandleMsg, InitMsg, PlaceCollectionOffer, PlaceOffer, QueryMsg, ReceiveNftMsg, SellNft};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary};
+    use cw721::Cw721ReceiveMsg;
+
+    fn sell_msg(list_price: Price) -> HandleMsg {
+        HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("AllowedNFT"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price, expires: None, reserved_for: None })).unwrap(),
+        })
+    }
+
+    #[test]
+    fn allow_list_path() {
+        let mut deps = mock_dependencies();
+        let msg = InitMsg {
+            count: 0,
+            name: String::from("curated marketplace"),
+            admin: None,
+            fee_bps: 0,
+            fee_recipient: None,
+            open_mode: Some(false),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // nothing can be listed until the admin allows it
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), sell_msg(cw20_price(5))).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionNotAllowed { .. }));
+
+        let update = HandleMsg::UpdateAllowList {
+            add_collections: vec![String::from("nftcontract"), String::from("othernft")],
+            remove_collections: vec![String::from("othernft")],
+            add_currencies: vec![String::from("cw20contract")],
+            remove_currencies: vec![],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), update.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), update).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::AllowedCollections { start_after: None, limit: None }).unwrap();
+        let allowed: AllowedCollectionsResponse = from_binary(&res).unwrap();
+        assert_eq!(allowed.collections, vec![Addr::unchecked("nftcontract")]);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::AllowedCurrencies { start_after: None, limit: None }).unwrap();
+        let allowed: AllowedCurrenciesResponse = from_binary(&res).unwrap();
+        assert_eq!(allowed.currencies, vec![String::from("cw20contract")]);
+
+        // only the allowed currency is accepted
+        let native = sell_msg(Price::Native(coin(100, "ujuno")));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), native).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyNotAllowed { currency } if currency == "ujuno"));
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), sell_msg(cw20_price(5))).unwrap();
+
+        // open mode accepts anything again
+        let open = HandleMsg::UpdateConfig { fee_bps: None, fee_recipient: None, open_mode: Some(true) };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), open).unwrap();
+        sell_nft(deps.as_mut(), "AnyNFT", Price::Native(coin(100, "ujuno")));
+    }
+
+    fn accept_msg(msg: ReceiveNftMsg) -> HandleMsg {
+        HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("owner"),
+            token_id: String::from("AllowedNFT"),
+            msg: to_binary(&msg).unwrap(),
+        })
+    }
+
+    #[test]
+    fn allow_list_bypass_rejected() {
+        let mut deps = mock_dependencies();
+        let msg = InitMsg {
+            count: 0,
+            name: String::from("curated marketplace"),
+            admin: None,
+            fee_bps: 0,
+            fee_recipient: None,
+            open_mode: Some(false),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let update = HandleMsg::UpdateAllowList {
+            add_collections: vec![String::from("nftcontract")],
+            remove_collections: vec![],
+            add_currencies: vec![String::from("cw20contract"), String::from("ujuno")],
+            remove_currencies: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), update).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), sell_msg(cw20_price(5))).unwrap();
+
+        // a listing can't move to a currency outside of the allow-list
+        let update_price = HandleMsg::UpdatePrice { offering_id: String::from("1"), list_price: Price::Native(coin(100, "uatom")) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), update_price).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyNotAllowed { currency } if currency == "uatom"));
+
+        // nor can offers be placed on other collections or in other currencies
+        let offer = |contract_addr: &str| {
+            HandleMsg::PlaceOffer(PlaceOffer {
+                contract_addr: contract_addr.to_string(),
+                token_id: String::from("AllowedNFT"),
+                expires: None,
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bidder", &coins(100, "ujuno")), offer("othernft")).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionNotAllowed { .. }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bidder", &coins(100, "uatom")), offer("nftcontract")).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyNotAllowed { currency } if currency == "uatom"));
+        let collection_offer = |contract_addr: &str| {
+            HandleMsg::PlaceCollectionOffer(PlaceCollectionOffer {
+                contract_addr: contract_addr.to_string(),
+                quantity: 1,
+                expires: None,
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bidder", &coins(100, "ujuno")), collection_offer("othernft")).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionNotAllowed { .. }));
+
+        // offers placed before a currency leaves the allow-list can't be accepted anymore
+        execute(deps.as_mut(), mock_env(), mock_info("bidder", &coins(100, "ujuno")), offer("nftcontract")).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("bidder", &coins(100, "ujuno")), collection_offer("nftcontract")).unwrap();
+        let update = HandleMsg::UpdateAllowList {
+            add_collections: vec![],
+            remove_collections: vec![],
+            add_currencies: vec![],
+            remove_currencies: vec![String::from("ujuno")],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), update).unwrap();
+        let accept = accept_msg(ReceiveNftMsg::AcceptOffer(AcceptOffer { offer_id: String::from("1") }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyNotAllowed { .. }));
+        let accept = accept_msg(ReceiveNftMsg::AcceptCollectionOffer(AcceptOffer { offer_id: String::from("1") }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyNotAllowed { .. }));
+    }
+}
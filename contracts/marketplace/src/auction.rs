@@ -0,0 +1,369 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
+use cw_storage_plus::Bound;
+use cw_utils::must_pay;
+
+use crate::allowlist::check_allowed;
+use crate::contract::{sale_payout, transfer_nft_msg, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::msg::{PlaceBid, StartAuction};
+use crate::package::{AuctionsResponse, Price, QueryAuctionResult};
+use crate::state::{increment_auctions, Auction, Bid, AUCTIONS};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Start an English auction for the nft sent with `SendNft`, `info.sender` is the nft contract
+ */
+pub fn try_start_auction(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: StartAuction,
+) -> Result<Response, ContractError> {
+    if msg.end_time <= env.block.time {
+        return Err(ContractError::InvalidEndTime {});
+    }
+
+    let reserve_price = msg.reserve_price.validate(deps.api)?;
+    check_allowed(deps.as_ref(), &info.sender, &reserve_price)?;
+
+    let id = increment_auctions(deps.storage)?.to_string();
+    let auction = Auction {
+        seller: deps.api.addr_validate(&rcv_msg.sender)?,
+        contract_addr: info.sender,
+        token_id: rcv_msg.token_id,
+        reserve_price,
+        end_time: msg.end_time,
+        min_increment: msg.min_increment,
+        highest_bid: None,
+    };
+    AUCTIONS.save(deps.storage, &id, &auction)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "start_auction")
+        .add_attribute("auction_id", id)
+        .add_attribute("original_contract", auction.contract_addr)
+        .add_attribute("seller", auction.seller)
+        .add_attribute("reserve_price", auction.reserve_price.to_string())
+        .add_attribute("end_time", auction.end_time.to_string())
+        .add_attribute("token_id", auction.token_id)
+    )
+}
+
+/**
+ * Bid on an Auction in a native coin with the funds attached to the message
+ */
+pub fn try_place_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    auction_id: String,
+) -> Result<Response, ContractError> {
+    let auction = AUCTIONS.load(deps.storage, &auction_id)?;
+    let amount = match &auction.reserve_price {
+        Price::Native(coin) => must_pay(&info, &coin.denom)?,
+        Price::Cw20(_) => return Err(ContractError::Cw20PriceOnly {}),
+    };
+    place_bid(deps, env, auction_id, auction, info.sender, amount)
+}
+
+/**
+ * Bid on an Auction in a CW20 token with the tokens sent through the `Send` hook
+ */
+pub fn try_receive_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: PlaceBid,
+) -> Result<Response, ContractError> {
+    let auction = AUCTIONS.load(deps.storage, &msg.auction_id)?;
+    match &auction.reserve_price {
+        Price::Cw20(coin) if coin.address != info.sender => {
+            return Err(ContractError::WrongToken {
+                expected: coin.address.to_string(),
+                received: info.sender.to_string(),
+            })
+        }
+        Price::Cw20(_) => {}
+        Price::Native(_) => return Err(ContractError::NativePriceOnly {}),
+    }
+    let bidder = deps.api.addr_validate(&rcv_msg.sender)?;
+    place_bid(deps, env, msg.auction_id, auction, bidder, rcv_msg.amount)
+}
+
+/**
+ * Record a bid escrowed by the marketplace and refund the bidder it outbids
+ */
+fn place_bid(
+    deps: DepsMut,
+    env: Env,
+    auction_id: String,
+    mut auction: Auction,
+    bidder: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if env.block.time >= auction.end_time {
+        return Err(ContractError::AuctionEnded {});
+    }
+    let min_bid = auction.min_bid();
+    if amount < min_bid {
+        return Err(ContractError::BidTooLow { min_bid });
+    }
+
+    let previous = auction.highest_bid.replace(Bid {
+        bidder: bidder.clone(),
+        amount,
+    });
+    AUCTIONS.save(deps.storage, &auction_id, &auction)?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "place_bid")
+        .add_attribute("auction_id", auction_id)
+        .add_attribute("bidder", bidder)
+        .add_attribute("amount", amount);
+    if let Some(previous) = previous {
+        res = res.add_message(auction.reserve_price.transfer_msg(&previous.bidder, previous.amount)?);
+    }
+    Ok(res)
+}
+
+/**
+ * Close an Auction after its end time. The nft goes to the highest bidder if the reserve price
+ * was reached, otherwise it is returned to the seller and the highest bid is refunded
+ */
+pub fn try_settle_auction(
+    deps: DepsMut,
+    env: Env,
+    auction_id: String,
+) -> Result<Response, ContractError> {
+    let auction = AUCTIONS.load(deps.storage, &auction_id)?;
+    if env.block.time < auction.end_time {
+        return Err(ContractError::AuctionNotEnded {});
+    }
+    AUCTIONS.remove(deps.storage, &auction_id);
+
+    let res = Response::new()
+        .add_attribute("action", "settle_auction")
+        .add_attribute("auction_id", auction_id)
+        .add_attribute("seller", auction.seller.clone())
+        .add_attribute("token_id", auction.token_id.clone())
+        .add_attribute("contract_addr", auction.contract_addr.clone());
+
+    match auction.highest_bid {
+        Some(bid) if bid.amount >= auction.reserve_price.amount() => {
+            let payout = sale_payout(
+                deps.as_ref(),
+                &auction.reserve_price,
+                bid.amount,
+                &auction.contract_addr,
+                &auction.token_id,
+                &auction.seller,
+            )?;
+            let paid_price = auction.reserve_price.with_amount(bid.amount);
+            record_sale(deps.storage, &env, &auction.contract_addr, &auction.token_id, &auction.seller, &bid.bidder, paid_price)?;
+            Ok(res
+                .add_attribute("result", "sold")
+                .add_attribute("buyer", bid.bidder.clone())
+                .add_attribute("paid_price", bid.amount)
+                .add_attribute("fee", payout.fee)
+                .add_attribute("royalty", payout.royalty)
+                .add_messages(payout.messages)
+                .add_message(transfer_nft_msg(&auction.contract_addr, &bid.bidder, &auction.token_id)?))
+        }
+        highest_bid => {
+            // reserve not met, everything goes back to its owner
+            let mut res = res
+                .add_attribute("result", "reserve_not_met")
+                .add_message(transfer_nft_msg(&auction.contract_addr, &auction.seller, &auction.token_id)?);
+            if let Some(bid) = highest_bid {
+                res = res.add_message(auction.reserve_price.transfer_msg(&bid.bidder, bid.amount)?);
+            }
+            Ok(res)
+        }
+    }
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_auction(deps: Deps, auction_id: String) -> StdResult<QueryAuctionResult> {
+    let auction = AUCTIONS.load(deps.storage, &auction_id)?;
+    Ok(parse_auction(auction_id, auction))
+}
+
+pub fn query_auctions(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AuctionsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let auctions = AUCTIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, auction)| parse_auction(id, auction)))
+        .collect::<StdResult<_>>()?;
+    Ok(AuctionsResponse { auctions })
+}
+
+fn parse_auction(id: String, auction: Auction) -> QueryAuctionResult {
+    QueryAuctionResult {
+        id,
+        token_id: auction.token_id,
+        contract_addr: auction.contract_addr,
+        seller: auction.seller,
+        reserve_price: auction.reserve_price,
+        end_time: auction.end_time,
+        min_increment: auction.min_increment,
+        highest_bid: auction.highest_bid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market};
+    use crate::contract::{execute, query};
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg, ReceiveNftMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, BankMsg, CosmosMsg, Timestamp};
+
+    fn start_auction(deps: DepsMut, reserve_price: Price) {
+        let msg = StartAuction {
+            reserve_price,
+            end_time: mock_env().block.time.plus_seconds(3600),
+            min_increment: Uint128::new(10),
+        };
+        let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("AuctionNFT"),
+            msg: to_binary(&ReceiveNftMsg::StartAuction(msg)).unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap();
+    }
+
+    fn env_at(time: Timestamp) -> Env {
+        let mut env = mock_env();
+        env.block.time = time;
+        env
+    }
+
+    #[test]
+    fn native_auction_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        start_auction(deps.as_mut(), Price::Native(coin(100, "ujuno")));
+
+        // first bid can be below the reserve price
+        let bid = HandleMsg::PlaceBid { auction_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(50, "ujuno")), bid.clone()).unwrap();
+
+        // an outbid must respect the minimum increment
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(55, "ujuno")), bid.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::BidTooLow { min_bid } if min_bid == Uint128::new(60)));
+
+        // the previous bidder is refunded when outbid
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(120, "ujuno")), bid.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(50, "ujuno") })
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Auction { auction_id: "1".to_string() }).unwrap();
+        let auction: QueryAuctionResult = from_binary(&res).unwrap();
+        assert_eq!(
+            auction.highest_bid,
+            Some(Bid { bidder: Addr::unchecked("bob"), amount: Uint128::new(120) })
+        );
+
+        // the auction can only be settled after its end time, and bids stop then
+        let settle = HandleMsg::SettleAuction { auction_id: "1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), settle.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::AuctionNotEnded {}));
+        let ended = env_at(auction.end_time);
+        let err = execute(deps.as_mut(), ended.clone(), mock_info("carol", &coins(500, "ujuno")), bid).unwrap_err();
+        assert!(matches!(err, ContractError::AuctionEnded {}));
+
+        // seller is paid and the nft goes to the winner
+        let res = execute(deps.as_mut(), ended, mock_info("anyone", &[]), settle).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(120, "ujuno") })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("bob"), "AuctionNFT").unwrap()
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Auctions { start_after: None, limit: None }).unwrap();
+        let auctions: AuctionsResponse = from_binary(&res).unwrap();
+        assert!(auctions.auctions.is_empty());
+    }
+
+    #[test]
+    fn cw20_auction_reserve_not_met() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        start_auction(deps.as_mut(), cw20_price(100));
+
+        let bid = |sender: &str, amount: u128| HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&ReceiveMsg::PlaceBid(PlaceBid { auction_id: "1".to_string() })).unwrap(),
+        });
+
+        // bids must come from the auction token
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), bid("alice", 80)).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), bid("alice", 80)).unwrap();
+
+        // reserve not met: nft back to the seller and the bid refunded
+        let settle = HandleMsg::SettleAuction { auction_id: "1".to_string() };
+        let ended = env_at(mock_env().block.time.plus_seconds(3600));
+        let res = execute(deps.as_mut(), ended, mock_info("anyone", &[]), settle).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("seller"), "AuctionNFT").unwrap()
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            cw20_price(0).transfer_msg(&Addr::unchecked("alice"), Uint128::new(80)).unwrap()
+        );
+    }
+
+    #[test]
+    fn settle_while_paused() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        start_auction(deps.as_mut(), Price::Native(coin(100, "ujuno")));
+        start_auction(deps.as_mut(), Price::Native(coin(100, "ujuno")));
+        let bid = HandleMsg::PlaceBid { auction_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(50, "ujuno")), bid).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), HandleMsg::Pause {}).unwrap();
+
+        // ended auctions still return the nft and the bid while the marketplace is paused
+        let ended = env_at(mock_env().block.time.plus_seconds(3600));
+        let settle = HandleMsg::SettleAuction { auction_id: "1".to_string() };
+        let res = execute(deps.as_mut(), ended.clone(), mock_info("anyone", &[]), settle).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("seller"), "AuctionNFT").unwrap(),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(50, "ujuno") }),
+            ]
+        );
+        let settle = HandleMsg::SettleAuction { auction_id: "2".to_string() };
+        let res = execute(deps.as_mut(), ended, mock_info("anyone", &[]), settle).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("seller"), "AuctionNFT").unwrap()]
+        );
+    }
+}
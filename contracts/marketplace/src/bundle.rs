@@ -0,0 +1,522 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Storage, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
+use cw_storage_plus::Bound;
+use cw_utils::must_pay;
+
+use crate::allowlist::check_allowed;
+use crate::contract::{clear_previous_listing, sale_payout, transfer_nft_msg, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::listing::listing_approved;
+use crate::msg::{AddToBundle, BuyBundle};
+use crate::package::{BundlesResponse, Price, QueryBundleResult};
+use crate::state::{increment_bundles, Bundle, BundleItem, BUNDLED_ITEMS, BUNDLES, MAX_BUNDLE_ITEMS};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Start an empty draft bundle owned by the sender
+ */
+pub fn try_create_bundle(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let id = increment_bundles(deps.storage)?.to_string();
+    let bundle = Bundle {
+        seller: info.sender,
+        items: vec![],
+        list_price: None,
+    };
+    BUNDLES.save(deps.storage, &id, &bundle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_bundle")
+        .add_attribute("bundle_id", id)
+        .add_attribute("seller", bundle.seller)
+    )
+}
+
+/**
+ * Add the nft sent with `SendNft` to a draft bundle, `info.sender` is the nft contract and
+ * `rcv_msg.sender` must be the bundle seller
+ */
+pub fn try_add_to_bundle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: AddToBundle,
+) -> Result<Response, ContractError> {
+    let item = BundleItem {
+        contract_addr: info.sender,
+        token_id: rcv_msg.token_id,
+        non_custodial: false,
+    };
+    let sender = deps.api.addr_validate(&rcv_msg.sender)?;
+    add_item(deps, &env, msg.bundle_id, sender, item)
+}
+
+/**
+ * Add an nft kept in the seller wallet to a draft bundle, the marketplace must be approved on it
+ */
+pub fn try_add_approved_to_bundle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    bundle_id: String,
+    contract_addr: String,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let item = BundleItem {
+        contract_addr: deps.api.addr_validate(&contract_addr)?,
+        token_id,
+        non_custodial: true,
+    };
+    if !listing_approved(deps.as_ref(), &env, &item.contract_addr, &item.token_id, &info.sender)? {
+        return Err(ContractError::NotApproved {});
+    }
+    add_item(deps, &env, bundle_id, info.sender, item)
+}
+
+/**
+ * Add an nft to a draft bundle of `sender`. The nft can't be in an offering or another bundle
+ * unless they can't sell it anymore
+ */
+fn add_item(
+    mut deps: DepsMut,
+    env: &Env,
+    bundle_id: String,
+    sender: Addr,
+    item: BundleItem,
+) -> Result<Response, ContractError> {
+    let mut bundle = BUNDLES.load(deps.storage, &bundle_id)?;
+    if bundle.seller != sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if bundle.list_price.is_some() {
+        return Err(ContractError::BundlePublished {});
+    }
+    if bundle.items.len() >= MAX_BUNDLE_ITEMS {
+        return Err(ContractError::BundleFull { max: MAX_BUNDLE_ITEMS });
+    }
+    clear_previous_listing(deps.branch(), env, &item.contract_addr, &item.token_id)?;
+    bundle.items.push(item.clone());
+    BUNDLES.save(deps.storage, &bundle_id, &bundle)?;
+    BUNDLED_ITEMS.save(deps.storage, (&item.contract_addr, &item.token_id), &bundle_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_to_bundle")
+        .add_attribute("bundle_id", bundle_id)
+        .add_attribute("contract_addr", item.contract_addr)
+        .add_attribute("token_id", item.token_id)
+    )
+}
+
+/**
+ * Put a draft bundle up for sale, seller only. Every collection of the bundle and its currency
+ * must be allowed on the marketplace
+ */
+pub fn try_publish_bundle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    bundle_id: String,
+    list_price: Price,
+) -> Result<Response, ContractError> {
+    let mut bundle = BUNDLES.load(deps.storage, &bundle_id)?;
+    if bundle.seller != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if bundle.list_price.is_some() {
+        return Err(ContractError::BundlePublished {});
+    }
+    if bundle.items.is_empty() {
+        return Err(ContractError::EmptyBundle {});
+    }
+    let list_price = list_price.validate(deps.api)?;
+    // each nft is paid its share of the price, none may be given away
+    let min = Uint128::from(bundle.items.len() as u64);
+    if list_price.amount() < min {
+        return Err(ContractError::BundlePriceTooLow { min });
+    }
+    for item in &bundle.items {
+        check_allowed(deps.as_ref(), &item.contract_addr, &list_price)?;
+        check_item(deps.as_ref(), &env, &bundle.seller, item)?;
+    }
+    bundle.list_price = Some(list_price.clone());
+    BUNDLES.save(deps.storage, &bundle_id, &bundle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "publish_bundle")
+        .add_attribute("bundle_id", bundle_id)
+        .add_attribute("seller", bundle.seller)
+        .add_attribute("list_price", list_price.to_string())
+        .add_attribute("items", bundle.items.len().to_string())
+    )
+}
+
+/**
+ * Buy a bundle listed in a native coin, paying with the funds attached to the message
+ */
+pub fn try_buy_bundle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    bundle_id: String,
+) -> Result<Response, ContractError> {
+    let bundle = BUNDLES.load(deps.storage, &bundle_id)?;
+    let paid = match &bundle.list_price {
+        Some(Price::Native(coin)) => must_pay(&info, &coin.denom)?,
+        Some(Price::Cw20(_)) => return Err(ContractError::Cw20PriceOnly {}),
+        None => return Err(ContractError::BundleNotPublished {}),
+    };
+    settle_bundle(deps, env, bundle_id, bundle, info.sender, paid)
+}
+
+/**
+ * Buy a bundle listed in a CW20 token with the tokens sent through the `Send` hook
+ */
+pub fn try_receive_buy_bundle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: BuyBundle,
+) -> Result<Response, ContractError> {
+    let bundle = BUNDLES.load(deps.storage, &msg.bundle_id)?;
+    match &bundle.list_price {
+        Some(Price::Cw20(coin)) if coin.address != info.sender => {
+            return Err(ContractError::WrongToken {
+                expected: coin.address.to_string(),
+                received: info.sender.to_string(),
+            })
+        }
+        Some(Price::Cw20(_)) => {}
+        Some(Price::Native(_)) => return Err(ContractError::NativePriceOnly {}),
+        None => return Err(ContractError::BundleNotPublished {}),
+    }
+    let buyer = deps.api.addr_validate(&rcv_msg.sender)?;
+    settle_bundle(deps, env, msg.bundle_id, bundle, buyer, rcv_msg.amount)
+}
+
+/**
+ * Complete the sale of a bundle: every nft goes to the buyer in the same transaction. Each nft
+ * is paid an equal share of the price, so the fee and its collection royalty are taken from it
+ */
+fn settle_bundle(
+    deps: DepsMut,
+    env: Env,
+    bundle_id: String,
+    bundle: Bundle,
+    buyer: Addr,
+    paid: Uint128,
+) -> Result<Response, ContractError> {
+    let list_price = bundle.list_price.clone().ok_or(ContractError::BundleNotPublished {})?;
+    let price = list_price.amount();
+    if paid < price {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    remove_bundle(deps.storage, &bundle_id, &bundle)?;
+
+    let count = Uint128::from(bundle.items.len() as u64);
+    let share = price / count;
+    let mut messages = vec![];
+    let (mut fee, mut royalty) = (Uint128::zero(), Uint128::zero());
+    for (i, item) in bundle.items.iter().enumerate() {
+        check_item(deps.as_ref(), &env, &bundle.seller, item)?;
+        // the last nft takes the rounding remainder
+        let amount = if i + 1 == bundle.items.len() { price - share * (count - Uint128::new(1)) } else { share };
+        let payout = sale_payout(deps.as_ref(), &list_price, amount, &item.contract_addr, &item.token_id, &bundle.seller)?;
+        fee += payout.fee;
+        royalty += payout.royalty;
+        messages.extend(payout.messages);
+        let item_price = list_price.with_amount(amount);
+        record_sale(deps.storage, &env, &item.contract_addr, &item.token_id, &bundle.seller, &buyer, item_price)?;
+    }
+    if paid > price {
+        messages.push(list_price.transfer_msg(&buyer, paid - price)?);
+    }
+    for item in &bundle.items {
+        messages.push(transfer_nft_msg(&item.contract_addr, &buyer, &item.token_id)?);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "buy_bundle")
+        .add_attribute("bundle_id", bundle_id)
+        .add_attribute("buyer", buyer)
+        .add_attribute("seller", bundle.seller)
+        .add_attribute("paid_price", list_price.to_string())
+        .add_attribute("fee", fee)
+        .add_attribute("royalty", royalty)
+        .add_messages(messages)
+    )
+}
+
+/**
+ * Remove a bundle, draft or for sale, and return every nft held by the marketplace to the seller
+ */
+pub fn try_withdraw_bundle(
+    deps: DepsMut,
+    info: MessageInfo,
+    bundle_id: String,
+) -> Result<Response, ContractError> {
+    let bundle = BUNDLES.load(deps.storage, &bundle_id)?;
+    if bundle.seller != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    remove_bundle(deps.storage, &bundle_id, &bundle)?;
+
+    let messages = bundle
+        .items
+        .iter()
+        .filter(|item| !item.non_custodial)
+        .map(|item| transfer_nft_msg(&item.contract_addr, &bundle.seller, &item.token_id))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_bundle")
+        .add_attribute("bundle_id", bundle_id)
+        .add_attribute("seller", bundle.seller)
+        .add_messages(messages)
+    )
+}
+
+// the items of a bundle may have been added to a newer bundle since, their entry is left to it
+fn remove_bundle(storage: &mut dyn Storage, bundle_id: &str, bundle: &Bundle) -> StdResult<()> {
+    BUNDLES.remove(storage, bundle_id);
+    for item in &bundle.items {
+        let key = (&item.contract_addr, item.token_id.as_str());
+        if BUNDLED_ITEMS.may_load(storage, key)?.as_deref() == Some(bundle_id) {
+            BUNDLED_ITEMS.remove(storage, key);
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Fail with AlreadyListed when `token_id` is in a bundle its seller can still sell. A bundle
+ * item left in the seller wallet stops counting once its owner changed or approval was revoked
+ */
+pub(crate) fn check_not_bundled(
+    deps: Deps,
+    env: &Env,
+    contract_addr: &Addr,
+    token_id: &str,
+) -> Result<(), ContractError> {
+    let bundle = match BUNDLED_ITEMS.may_load(deps.storage, (contract_addr, token_id))? {
+        Some(bundle_id) => BUNDLES.load(deps.storage, &bundle_id)?,
+        None => return Ok(()),
+    };
+    let item = bundle.items.iter().find(|i| i.contract_addr == *contract_addr && i.token_id == token_id);
+    match item {
+        Some(item) if check_item(deps, env, &bundle.seller, item).is_ok() => Err(ContractError::AlreadyListed {}),
+        _ => Ok(()),
+    }
+}
+
+// an nft left in the seller wallet must still be transferable by the marketplace
+fn check_item(deps: Deps, env: &Env, seller: &Addr, item: &BundleItem) -> Result<(), ContractError> {
+    if item.non_custodial && !listing_approved(deps, env, &item.contract_addr, &item.token_id, seller)? {
+        return Err(ContractError::StaleListing {});
+    }
+    Ok(())
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_bundle(deps: Deps, bundle_id: String) -> StdResult<QueryBundleResult> {
+    let bundle = BUNDLES.load(deps.storage, &bundle_id)?;
+    Ok(parse_bundle(bundle_id, bundle))
+}
+
+pub fn query_bundles(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BundlesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let bundles = BUNDLES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, bundle)| parse_bundle(id, bundle)))
+        .collect::<StdResult<_>>()?;
+    Ok(BundlesResponse { bundles })
+}
+
+fn parse_bundle(id: String, bundle: Bundle) -> QueryBundleResult {
+    QueryBundleResult {
+        id,
+        seller: bundle.seller,
+        items: bundle.items,
+        list_price: bundle.list_price,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market};
+    use crate::contract::{execute, query};
+    use crate::listing::tests::mock_nft;
+    use crate::msg::{HandleMsg, ListNft, QueryMsg, ReceiveMsg, ReceiveNftMsg, SellNft};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, BankMsg, CosmosMsg};
+
+    fn add_nft(deps: DepsMut, sender: &str, token_id: &str) -> Result<Response, ContractError> {
+        let msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from(sender),
+            token_id: String::from(token_id),
+            msg: to_binary(&ReceiveNftMsg::AddToBundle(AddToBundle { bundle_id: String::from("1") })).unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("nftcontract", &[]), msg)
+    }
+
+    fn publish(deps: DepsMut, list_price: Price) -> Result<Response, ContractError> {
+        let msg = HandleMsg::PublishBundle { bundle_id: String::from("1"), list_price };
+        execute(deps, mock_env(), mock_info("seller", &[]), msg)
+    }
+
+    #[test]
+    fn native_bundle_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::CreateBundle {}).unwrap();
+
+        let err = publish(deps.as_mut(), Price::Native(coin(100, "ujuno"))).unwrap_err();
+        assert!(matches!(err, ContractError::EmptyBundle {}));
+        let err = add_nft(deps.as_mut(), "anyone", "Piece1").unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        add_nft(deps.as_mut(), "seller", "Piece1").unwrap();
+        add_nft(deps.as_mut(), "seller", "Piece2").unwrap();
+
+        // a draft can't be bought, a published bundle can't change
+        let buy = HandleMsg::BuyBundle { bundle_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::BundleNotPublished {}));
+        publish(deps.as_mut(), Price::Native(coin(101, "ujuno"))).unwrap();
+        let err = add_nft(deps.as_mut(), "seller", "Piece3").unwrap_err();
+        assert!(matches!(err, ContractError::BundlePublished {}));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Bundle { bundle_id: String::from("1") }).unwrap();
+        let bundle: QueryBundleResult = from_binary(&res).unwrap();
+        assert_eq!(bundle.items.len(), 2);
+
+        // every piece goes to the buyer, each paid its share of the price
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(101, "ujuno")), buy).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(50, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(51, "ujuno") }),
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Piece1").unwrap(),
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Piece2").unwrap(),
+            ]
+        );
+        assert!(BUNDLES.may_load(&deps.storage, "1").unwrap().is_none());
+    }
+
+    #[test]
+    fn cw20_bundle_withdrawn() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::CreateBundle {}).unwrap();
+        add_nft(deps.as_mut(), "seller", "Piece1").unwrap();
+        add_nft(deps.as_mut(), "seller", "Piece2").unwrap();
+        publish(deps.as_mut(), cw20_price(10)).unwrap();
+
+        // payment must come from the bundle token
+        let buy = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("buyer"),
+            amount: Uint128::new(10),
+            msg: to_binary(&ReceiveMsg::BuyBundle(BuyBundle { bundle_id: String::from("1") })).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), buy).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+
+        // withdrawal returns every piece
+        let withdraw = HandleMsg::WithdrawBundle { bundle_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), withdraw.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), withdraw).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("seller"), "Piece2").unwrap()
+        );
+    }
+
+    #[test]
+    fn bundle_price_covers_every_nft() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::CreateBundle {}).unwrap();
+        add_nft(deps.as_mut(), "seller", "Piece1").unwrap();
+        add_nft(deps.as_mut(), "seller", "Piece2").unwrap();
+
+        // a price below the nft count would leave some nft unpaid
+        let err = publish(deps.as_mut(), Price::Native(coin(1, "ujuno"))).unwrap_err();
+        assert!(matches!(err, ContractError::BundlePriceTooLow { min } if min == Uint128::new(2)));
+        publish(deps.as_mut(), Price::Native(coin(2, "ujuno"))).unwrap();
+        let buy = HandleMsg::BuyBundle { bundle_id: String::from("1") };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(2, "ujuno")), buy).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs[..2],
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(1, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(1, "ujuno") }),
+            ]
+        );
+    }
+
+    #[test]
+    fn nft_listed_once_across_offerings_and_bundles() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_nft(&mut deps, "seller", true, false);
+        let list = HandleMsg::ListNft(ListNft {
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from("WalletNFT"),
+            list_price: Price::Native(coin(100, "ujuno")),
+            expires: None,
+        });
+        let add_approved = |bundle_id: &str| HandleMsg::AddApprovedToBundle {
+            bundle_id: bundle_id.to_string(),
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from("WalletNFT"),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::CreateBundle {}).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::CreateBundle {}).unwrap();
+
+        // a listed nft can't be bundled
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), add_approved("1")).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyListed {}));
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::WithdrawNft { offering_id: String::from("1") }).unwrap();
+
+        // nor can a bundled one be listed, with or without custody, or bundled again
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), add_approved("1")).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyListed {}));
+        let sell = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("WalletNFT"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price: Price::Native(coin(100, "ujuno")), expires: None, reserved_for: None })).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), sell).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyListed {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), add_approved("2")).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyListed {}));
+
+        // a new owner isn't held back by the stale bundle item
+        mock_nft(&mut deps, "newowner", true, false);
+        execute(deps.as_mut(), mock_env(), mock_info("newowner", &[]), list).unwrap();
+
+        // withdrawing the bundle frees the nft
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), HandleMsg::WithdrawBundle { bundle_id: String::from("1") }).unwrap();
+        assert!(BUNDLED_ITEMS.keys(&deps.storage, None, None, Order::Ascending).next().is_none());
+    }
+}
@@ -0,0 +1,354 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Uint128};
+use cw20::{Cw20CoinVerified, Cw20ReceiveMsg};
+use cw721::Cw721ReceiveMsg;
+use cw_utils::one_coin;
+
+use crate::allowlist::check_allowed;
+use crate::contract::{check_cw20_sender, sale_payout, transfer_nft_msg, MAX_SCAN};
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::msg::{AcceptOffer, PlaceCollectionOffer};
+use crate::package::{BestCollectionOfferResponse, Price, QueryCollectionOfferResult};
+use crate::state::{collection_offers, increment_collection_offers, CollectionOffer};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Bid on a collection, escrowing the single native coin attached to the message
+ */
+pub fn try_place_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: PlaceCollectionOffer,
+) -> Result<Response, ContractError> {
+    let escrow = Price::Native(one_coin(&info)?);
+    save_collection_offer(deps, env, info.sender, escrow, msg)
+}
+
+/**
+ * Bid on a collection, escrowing the CW20 tokens sent through the `Send` hook
+ */
+pub fn try_receive_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: PlaceCollectionOffer,
+) -> Result<Response, ContractError> {
+    check_cw20_sender(deps.as_ref(), &info.sender)?;
+    let bidder = deps.api.addr_validate(&rcv_msg.sender)?;
+    let escrow = Price::Cw20(Cw20CoinVerified {
+        address: info.sender,
+        amount: rcv_msg.amount,
+    });
+    save_collection_offer(deps, env, bidder, escrow, msg)
+}
+
+fn save_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    bidder: Addr,
+    escrow: Price,
+    msg: PlaceCollectionOffer,
+) -> Result<Response, ContractError> {
+    let quantity = Uint128::from(msg.quantity);
+    if quantity.is_zero() || !(escrow.amount() % quantity).is_zero() {
+        return Err(ContractError::InvalidQuantity {});
+    }
+    let price = escrow.with_amount(escrow.amount() / quantity);
+    if price.amount().is_zero() {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    let expires = msg.expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::OfferExpired {});
+    }
+    let contract_addr = deps.api.addr_validate(&msg.contract_addr)?;
+    check_allowed(deps.as_ref(), &contract_addr, &price)?;
+
+    let id = increment_collection_offers(deps.storage)?.to_string();
+    let offer = CollectionOffer {
+        bidder,
+        contract_addr,
+        price,
+        quantity: msg.quantity,
+        expires,
+    };
+    collection_offers().save(deps.storage, &id, &offer)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "place_collection_offer")
+        .add_attribute("offer_id", id)
+        .add_attribute("bidder", offer.bidder)
+        .add_attribute("contract_addr", offer.contract_addr)
+        .add_attribute("price", offer.price.to_string())
+        .add_attribute("quantity", offer.quantity.to_string())
+        .add_attribute("expires", offer.expires.to_string())
+    )
+}
+
+/**
+ * Cancel a collection offer and refund the escrow of its unfilled quantity. The bidder can
+ * cancel at any time, anyone else only once the offer has expired
+ */
+pub fn try_cancel_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer_id: String,
+) -> Result<Response, ContractError> {
+    let offer = collection_offers().load(deps.storage, &offer_id)?;
+    if offer.bidder != info.sender && !offer.expires.is_expired(&env.block) {
+        return Err(ContractError::Unauthorized {});
+    }
+    collection_offers().remove(deps.storage, &offer_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_collection_offer")
+        .add_attribute("offer_id", offer_id)
+        .add_attribute("bidder", offer.bidder.clone())
+        .add_attribute("refund", offer.escrow())
+        .add_message(offer.price.transfer_msg(&offer.bidder, offer.escrow())?)
+    )
+}
+
+/**
+ * Fill one unit of a collection offer with the nft sent with `SendNft`, `info.sender` is the nft
+ * contract and `rcv_msg.sender` the owner being paid
+ */
+pub fn try_accept_collection_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: AcceptOffer,
+) -> Result<Response, ContractError> {
+    let mut offer = collection_offers().load(deps.storage, &msg.offer_id)?;
+    if offer.contract_addr != info.sender {
+        return Err(ContractError::OfferTokenMismatch {});
+    }
+    if offer.expires.is_expired(&env.block) {
+        return Err(ContractError::OfferExpired {});
+    }
+    // the collection or the currency may have left the allow-lists since the offer was placed
+    check_allowed(deps.as_ref(), &offer.contract_addr, &offer.price)?;
+
+    offer.quantity -= 1;
+    if offer.quantity == 0 {
+        collection_offers().remove(deps.storage, &msg.offer_id)?;
+    } else {
+        collection_offers().save(deps.storage, &msg.offer_id, &offer)?;
+    }
+
+    let seller = deps.api.addr_validate(&rcv_msg.sender)?;
+    let amount = offer.price.amount();
+    let payout = sale_payout(
+        deps.as_ref(),
+        &offer.price,
+        amount,
+        &offer.contract_addr,
+        &rcv_msg.token_id,
+        &seller,
+    )?;
+    record_sale(deps.storage, &env, &offer.contract_addr, &rcv_msg.token_id, &seller, &offer.bidder, offer.price.clone())?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_collection_offer")
+        .add_attribute("offer_id", msg.offer_id)
+        .add_attribute("buyer", offer.bidder.clone())
+        .add_attribute("seller", seller)
+        .add_attribute("paid_price", amount)
+        .add_attribute("fee", payout.fee)
+        .add_attribute("royalty", payout.royalty)
+        .add_attribute("remaining", offer.quantity.to_string())
+        .add_attribute("token_id", rcv_msg.token_id.clone())
+        .add_attribute("contract_addr", offer.contract_addr.clone())
+        .add_messages(payout.messages)
+        .add_message(transfer_nft_msg(&offer.contract_addr, &offer.bidder, &rcv_msg.token_id)?)
+    )
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_collection_offer(deps: Deps, offer_id: String) -> StdResult<QueryCollectionOfferResult> {
+    let offer = collection_offers().load(deps.storage, &offer_id)?;
+    Ok(parse_collection_offer(offer_id, offer))
+}
+
+/**
+ * Highest price offered on a collection in one currency, skipping offers that have expired
+ * but were not cancelled yet. Only the MAX_SCAN best offers are looked at, anyone can cancel the
+ * expired ones to refund their bidder
+ */
+pub fn query_best_collection_offer(
+    deps: Deps,
+    env: Env,
+    contract_addr: String,
+    currency: String,
+) -> StdResult<BestCollectionOfferResponse> {
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+
+    let offer = collection_offers()
+        .idx
+        .price
+        .sub_prefix((contract_addr, currency))
+        .range(deps.storage, None, None, Order::Descending)
+        .take(MAX_SCAN)
+        .find(|item| match item {
+            Ok((_, offer)) => !offer.expires.is_expired(&env.block),
+            Err(_) => true,
+        })
+        .transpose()?
+        .map(|(id, offer)| parse_collection_offer(id, offer));
+    Ok(BestCollectionOfferResponse { offer })
+}
+
+fn parse_collection_offer(id: String, offer: CollectionOffer) -> QueryCollectionOfferResult {
+    QueryCollectionOfferResult {
+        id,
+        bidder: offer.bidder,
+        contract_addr: offer.contract_addr,
+        price: offer.price,
+        quantity: offer.quantity,
+        expires: offer.expires,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market, mock_cw20};
+    use crate::contract::{execute, query};
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg, ReceiveNftMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, BankMsg, CosmosMsg};
+    use cw_utils::Expiration;
+
+    fn place_msg(quantity: u32, expires: Option<Expiration>) -> PlaceCollectionOffer {
+        PlaceCollectionOffer {
+            contract_addr: String::from("nftcontract"),
+            quantity,
+            expires,
+        }
+    }
+
+    fn accept_msg(sender: &str, token_id: &str, offer_id: &str) -> HandleMsg {
+        HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from(sender),
+            token_id: String::from(token_id),
+            msg: to_binary(&ReceiveNftMsg::AcceptCollectionOffer(AcceptOffer { offer_id: String::from(offer_id) })).unwrap(),
+        })
+    }
+
+    fn best_offer(deps: Deps, env: Env, currency: &str) -> Option<QueryCollectionOfferResult> {
+        let msg = QueryMsg::BestCollectionOffer {
+            contract_addr: String::from("nftcontract"),
+            currency: String::from(currency),
+        };
+        let res: BestCollectionOfferResponse = from_binary(&query(deps, env, msg).unwrap()).unwrap();
+        res.offer
+    }
+
+    #[test]
+    fn native_collection_offer_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        // escrow must split evenly over the quantity
+        let msg = HandleMsg::PlaceCollectionOffer(place_msg(3, None));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "ujuno")), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidQuantity {}));
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(90, "ujuno")), msg).unwrap();
+
+        let expires = Some(Expiration::AtHeight(mock_env().block.height + 10));
+        let msg = HandleMsg::PlaceCollectionOffer(place_msg(1, expires));
+        execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(50, "ujuno")), msg).unwrap();
+
+        // bob bids the most until his offer expires
+        let best = best_offer(deps.as_ref(), mock_env(), "ujuno").unwrap();
+        assert_eq!((best.id.as_str(), best.price.clone()), ("2", Price::Native(coin(50, "ujuno"))));
+        let mut later = mock_env();
+        later.block.height += 10;
+        let best = best_offer(deps.as_ref(), later, "ujuno").unwrap();
+        assert_eq!((best.id.as_str(), best.quantity), ("1", 3));
+        assert_eq!(best_offer(deps.as_ref(), mock_env(), "uatom"), None);
+
+        // only the collection's nfts can fill it, one unit per nft
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othernft", &[]), accept_msg("owner", "A", "1")).unwrap_err();
+        assert!(matches!(err, ContractError::OfferTokenMismatch {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept_msg("owner", "A", "1")).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "owner".to_string(), amount: coins(30, "ujuno") })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("alice"), "A").unwrap()
+        );
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept_msg("owner", "B", "1")).unwrap();
+
+        // cancelling refunds the unfilled unit only
+        let cancel = HandleMsg::CancelCollectionOffer { offer_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), cancel).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(30, "ujuno") })
+        );
+    }
+
+    #[test]
+    fn cw20_collection_offer_filled() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_cw20(&mut deps);
+
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("alice"),
+            amount: Uint128::new(40),
+            msg: to_binary(&ReceiveMsg::PlaceCollectionOffer(place_msg(2, None))).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), msg).unwrap();
+        let best = best_offer(deps.as_ref(), mock_env(), "cw20contract").unwrap();
+        assert_eq!(best.price, cw20_price(20));
+
+        // the offer is gone once its whole quantity is filled
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept_msg("owner", "A", "1")).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept_msg("owner", "B", "1")).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cw20_price(0).transfer_msg(&Addr::unchecked("owner"), Uint128::new(20)).unwrap()
+        );
+        assert_eq!(best_offer(deps.as_ref(), mock_env(), "cw20contract"), None);
+    }
+
+    #[test]
+    fn expired_collection_offers_pruned() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let msg = HandleMsg::PlaceCollectionOffer(place_msg(1, None));
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(10, "ujuno")), msg).unwrap();
+        let expires = Some(Expiration::AtHeight(mock_env().block.height + 1));
+        for _ in 0..MAX_SCAN {
+            let msg = HandleMsg::PlaceCollectionOffer(place_msg(1, expires));
+            execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(50, "ujuno")), msg).unwrap();
+        }
+
+        // the scan stops at MAX_SCAN expired offers
+        let mut later = mock_env();
+        later.block.height += 1;
+        assert_eq!(best_offer(deps.as_ref(), later.clone(), "ujuno"), None);
+
+        // anyone can prune an expired offer, its bidder is refunded
+        let cancel = HandleMsg::CancelCollectionOffer { offer_id: String::from("2") };
+        let res = execute(deps.as_mut(), later.clone(), mock_info("anyone", &[]), cancel).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(50, "ujuno") })
+        );
+        let best = best_offer(deps.as_ref(), later, "ujuno").unwrap();
+        assert_eq!(best.id, "1");
+    }
+}
@@ -4,23 +4,66 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, from_binary, to_binary, Api, Binary, CosmosMsg, Deps, DepsMut, Env, 
-    MessageInfo, Response, StdResult, Order, Querier, Storage, WasmMsg, SubMsg,
+    attr, from_binary, to_binary, Addr, Api, Binary, CosmosMsg, Deps, DepsMut, Env, Event,
+    MessageInfo, Response, StdError, StdResult, Order, Querier, Storage, Timestamp, Uint128, WasmMsg, SubMsg,
 };
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-use cw2::set_contract_version;
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, TokenInfoResponse};
 use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use cw_storage_plus::{Bound, Map, MultiIndex};
+use cw_utils::{must_pay, Expiration};
+use rvn_base::{QueryMsg as Cw721QueryMsg, RoyaltiesInfoResponse};
+use semver::Version;
 use std::str::from_utf8;
 
-use crate::package::{ContractInfoResponse, OfferingResponse, QueryOfferingResult};
+use crate::allowlist::{check_allowed, query_allowed_collections, query_allowed_currencies, try_update_allow_list};
+use crate::auction::{
+    query_auction, query_auctions, try_place_bid, try_receive_bid, try_settle_auction, try_start_auction,
+};
+use crate::bundle::{
+    check_not_bundled, query_bundle, query_bundles, try_add_approved_to_bundle, try_add_to_bundle, try_buy_bundle,
+    try_create_bundle, try_publish_bundle, try_receive_buy_bundle, try_withdraw_bundle,
+};
+use crate::collection_offer::{
+    query_best_collection_offer, query_collection_offer, try_accept_collection_offer,
+    try_cancel_collection_offer, try_place_collection_offer, try_receive_collection_offer,
+};
+use crate::rental::{query_rental, query_rentals, try_cancel_rental, try_list_rental, try_receive_rent, try_rent};
+use crate::lazy_mint::{query_lazy_mint_creator, query_voucher_redeemed, try_cancel_voucher, try_receive_redeem_voucher, try_redeem_voucher, try_set_lazy_mint_creator};
+use crate::signed::{query_nonce_used, try_buy_signed, try_cancel_signed_orders, try_receive_buy_signed};
+use crate::swap::{query_swap, query_swaps, try_cancel_swap, try_create_swap, try_deposit_to_swap, try_receive_create_swap};
+use crate::sweep::{try_buy_many, try_receive_buy_many};
+use crate::history::{
+    query_all_collection_stats, query_collection_stats, query_trades, query_trades_by_collection, record_sale,
+};
+use crate::listing::{listing_approved, try_list_nft, try_prune_listing};
+use crate::offer::{
+    query_offer, query_offers_by_bidder, query_offers_by_token, try_accept_offer, try_cancel_offer,
+    try_place_offer, try_receive_offer,
+};
+use crate::package::{ContractInfoResponse, FloorPriceResponse, OfferingByTokenResponse, OfferingResponse, OfferingsByPriceResponse, Price, QueryOfferingResult};
 use crate::error::ContractError;
-use crate::msg::{CountResponse, ExecuteMsg, InitMsg, InstantiateMsg, QueryMsg, HandleMsg, SellNft, BuyNft};
-use crate::state::{State, STATE, CONTRACT_INFO, OFFERINGS, Offering, increment_offerings};
+use crate::msg::{
+    CountResponse, ExecuteMsg, InitMsg, InstantiateMsg, MigrateMsg, QueryMsg, HandleMsg, ReceiveMsg, ReceiveNftMsg,
+    SellNft, SellDutch, BuyNft, SortOrder,
+};
+use crate::state::{
+    State, STATE, CONTRACT_INFO, CONFIG, BPS_DENOMINATOR, MAX_FEE_BPS, Config, Offering, PriceDecline,
+    increment_offerings, offerings, LegacyOffering, StoredOffering, OFFERINGS_COUNT,
+};
+
 
 
+// pagination of list queries
+pub(crate) const DEFAULT_LIMIT: u32 = 10;
+pub(crate) const MAX_LIMIT: u32 = 30;
+// queries skipping expired offerings or offers read at most MAX_SCAN entries per page, expired
+// offerings can be removed by anyone with ReclaimExpired and expired collection offers with
+// CancelCollectionOffer
+pub(crate) const MAX_SCAN: usize = 300;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:marketplace";
@@ -38,11 +81,83 @@ pub fn instantiate(
     msg: InitMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    let info = ContractInfoResponse { name: msg.name};
-    CONTRACT_INFO.save(deps.storage, &info)?;
+    let contract_info = ContractInfoResponse { name: msg.name};
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    validate_fee(msg.fee_bps)?;
+    let admin = match msg.admin {
+        Some(admin) => deps.api.addr_validate(&admin)?,
+        None => info.sender,
+    };
+    let fee_recipient = match msg.fee_recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => admin.clone(),
+    };
+    let config = Config {
+        admin,
+        fee_bps: msg.fee_bps,
+        fee_recipient,
+        open_mode: msg.open_mode.unwrap_or(true),
+        pending_admin: None,
+        paused: false,
+    };
+    CONFIG.save(deps.storage, &config)?;
+
     Ok(Response::default())
 }
 
+/**
+ * Migrate from an older marketplace: offerings stored before 0.2.0 are rewritten to the
+ * current Offering and indexed, and the config is created if the marketplace predates it
+ */
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate { previous_contract: stored.contract });
+    }
+    let cannot_migrate = || ContractError::CannotMigrateVersion { previous_version: stored.version.clone() };
+    let previous = Version::parse(&stored.version).map_err(|_| cannot_migrate())?;
+    let current = Version::parse(CONTRACT_VERSION).map_err(|_| cannot_migrate())?;
+    if previous > current {
+        return Err(cannot_migrate());
+    }
+
+    if CONFIG.may_load(deps.storage)?.is_none() {
+        let admin = deps.api.addr_validate(&msg.admin.ok_or(ContractError::MigrateAdminRequired {})?)?;
+        let fee_bps = msg.fee_bps.unwrap_or_default();
+        validate_fee(fee_bps)?;
+        let fee_recipient = match msg.fee_recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => admin.clone(),
+        };
+        let config = Config { admin, fee_bps, fee_recipient, open_mode: true, pending_admin: None, paused: false };
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    let mut migrated = 0u64;
+    if previous < Version::new(0, 2, 0) {
+        // the records were saved in a plain map, without any index entry
+        let raw: Map<&str, StoredOffering> = Map::new("offerings");
+        let stored_offerings = raw
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, stored) in stored_offerings {
+            raw.remove(deps.storage, &id);
+            offerings::<String>().save(deps.storage, &id, &stored.into())?;
+            migrated += 1;
+        }
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_version", stored.version)
+        .add_attribute("version", CONTRACT_VERSION)
+        .add_attribute("migrated_offerings", migrated.to_string())
+    )
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  */
@@ -50,99 +165,381 @@ pub fn instantiate(
 #[entry_point]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: HandleMsg,
 ) -> Result<Response, ContractError> {
+    if blocked_when_paused(&msg) && CONFIG.load(deps.storage)?.paused {
+        return Err(ContractError::Paused {});
+    }
     match msg {
         HandleMsg::Increment {} => Ok(Response::new().add_attribute("status", "OK").add_attribute("messages", String::from("Increment Called"))),
         HandleMsg::Reset {} => Ok(Response::new().add_attribute("status", "OK").add_attribute("messages", String::from("Reset Called"))),
         HandleMsg::WithdrawNft { offering_id } => try_withdraw(deps, info, offering_id),
-        HandleMsg::Receive(msg) => try_receive(deps, info, msg),
-        HandleMsg::ReceiveNft(msg) => try_receive_nft(deps, info, msg),
+        HandleMsg::ListNft(msg) => try_list_nft(deps, env, info, msg),
+        HandleMsg::PruneListing { offering_id } => try_prune_listing(deps, env, offering_id),
+        HandleMsg::UpdatePrice { offering_id, list_price } => try_update_price(deps, info, offering_id, list_price),
+        HandleMsg::ReclaimExpired { offering_id } => try_reclaim_expired(deps, env, offering_id),
+        HandleMsg::BuyNft { offering_id } => try_buy_nft(deps, env, info, offering_id),
+        HandleMsg::BuyMany(msg) => try_buy_many(deps, env, info, msg),
+        HandleMsg::UpdateConfig { fee_bps, fee_recipient, open_mode } => {
+            try_update_config(deps, info, fee_bps, fee_recipient, open_mode)
+        }
+        HandleMsg::CreateBundle {} => try_create_bundle(deps, info),
+        HandleMsg::AddApprovedToBundle { bundle_id, contract_addr, token_id } => {
+            try_add_approved_to_bundle(deps, env, info, bundle_id, contract_addr, token_id)
+        }
+        HandleMsg::PublishBundle { bundle_id, list_price } => try_publish_bundle(deps, env, info, bundle_id, list_price),
+        HandleMsg::BuyBundle { bundle_id } => try_buy_bundle(deps, env, info, bundle_id),
+        HandleMsg::WithdrawBundle { bundle_id } => try_withdraw_bundle(deps, info, bundle_id),
+        HandleMsg::ProposeAdmin { admin } => try_propose_admin(deps, info, admin),
+        HandleMsg::AcceptAdmin {} => try_accept_admin(deps, info),
+        HandleMsg::Pause {} => try_set_paused(deps, info, true),
+        HandleMsg::Unpause {} => try_set_paused(deps, info, false),
+        HandleMsg::UpdateAllowList { add_collections, remove_collections, add_currencies, remove_currencies } => {
+            try_update_allow_list(deps, info, add_collections, remove_collections, add_currencies, remove_currencies)
+        }
+        HandleMsg::PlaceBid { auction_id } => try_place_bid(deps, env, info, auction_id),
+        HandleMsg::SettleAuction { auction_id } => try_settle_auction(deps, env, auction_id),
+        HandleMsg::PlaceOffer(msg) => try_place_offer(deps, env, info, msg),
+        HandleMsg::CancelOffer { offer_id } => try_cancel_offer(deps, env, info, offer_id),
+        HandleMsg::PlaceCollectionOffer(msg) => try_place_collection_offer(deps, env, info, msg),
+        HandleMsg::CancelCollectionOffer { offer_id } => try_cancel_collection_offer(deps, env, info, offer_id),
+        HandleMsg::ListRental(msg) => try_list_rental(deps, env, info, msg),
+        HandleMsg::CancelRental { rental_id } => try_cancel_rental(deps, info, rental_id),
+        HandleMsg::Rent(msg) => try_rent(deps, env, info, msg),
+        HandleMsg::BuySigned(msg) => try_buy_signed(deps, env, info, msg),
+        HandleMsg::CancelSignedOrders { nonces } => try_cancel_signed_orders(deps, info, nonces),
+        HandleMsg::SetLazyMintCreator { contract_addr, creator } => try_set_lazy_mint_creator(deps, info, contract_addr, creator),
+        HandleMsg::RedeemVoucher(msg) => try_redeem_voucher(deps, env, info, msg),
+        HandleMsg::CancelVoucher { voucher } => try_cancel_voucher(deps, env, info, voucher),
+        HandleMsg::CreateSwap(msg) => try_create_swap(deps, info, msg),
+        HandleMsg::CancelSwap { swap_id } => try_cancel_swap(deps, info, swap_id),
+        HandleMsg::Receive(msg) => try_receive(deps, env, info, msg),
+        HandleMsg::ReceiveNft(msg) => try_receive_nft(deps, env, info, msg),
     }
 }
 
+/**
+ * Messages that list or sell an nft, or escrow funds for a sale, are refused while the
+ * marketplace is paused. Withdrawals, refunds, cancellations and auction settlements stay open, as
+ * settling is the only way out of an ended auction for its nft and bids
+ */
+fn blocked_when_paused(msg: &HandleMsg) -> bool {
+    matches!(
+        msg,
+        HandleMsg::BuyNft { .. }
+            | HandleMsg::BuyMany(_)
+            | HandleMsg::ListNft(_)
+            | HandleMsg::AddApprovedToBundle { .. }
+            | HandleMsg::PublishBundle { .. }
+            | HandleMsg::BuyBundle { .. }
+            | HandleMsg::PlaceBid { .. }
+            | HandleMsg::PlaceOffer(_)
+            | HandleMsg::PlaceCollectionOffer(_)
+            | HandleMsg::ListRental(_)
+            | HandleMsg::Rent(_)
+            | HandleMsg::BuySigned(_)
+            | HandleMsg::RedeemVoucher(_)
+            | HandleMsg::CreateSwap(_)
+            | HandleMsg::Receive(_)
+            | HandleMsg::ReceiveNft(_)
+    )
+}
+
 // =================================== Message Handlers ========================================
 
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
+ * Handle a CW20 payment. The payer calls `Cw20ExecuteMsg::Send` on the token contract,
+ * so `info.sender` is the token and the marketplace already holds `rcv_msg.amount`.
  */
 pub fn try_receive(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     rcv_msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    let msg: BuyNft = from_binary(&rcv_msg.msg)?;
+    if rcv_msg.msg.is_empty() {
+        return Err(ContractError::NoData {});
+    }
+    match from_binary(&rcv_msg.msg)? {
+        ReceiveMsg::BuyNft(msg) => try_receive_buy_nft(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::PlaceBid(msg) => try_receive_bid(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::PlaceOffer(msg) => try_receive_offer(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::PlaceCollectionOffer(msg) => try_receive_collection_offer(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::BuyBundle(msg) => try_receive_buy_bundle(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::BuyMany(msg) => try_receive_buy_many(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::CreateSwap(msg) => try_receive_create_swap(deps, info, rcv_msg, msg),
+        ReceiveMsg::Rent(msg) => try_receive_rent(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::BuySigned(msg) => try_receive_buy_signed(deps, env, info, rcv_msg, msg),
+        ReceiveMsg::RedeemVoucher(msg) => try_receive_redeem_voucher(deps, env, info, rcv_msg, msg),
+    }
+}
+
+/**
+ * Messages escrowing whatever CW20 they receive, rather than the token of a listing, first check
+ * that `info.sender` is a token contract. Otherwise an account calling `Receive` directly with a
+ * made-up Cw20ReceiveMsg would escrow tokens that don't exist
+ */
+pub(crate) fn check_cw20_sender(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    let token_info: StdResult<TokenInfoResponse> = deps.querier.query_wasm_smart(sender, &Cw20QueryMsg::TokenInfo {});
+    match token_info {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ContractError::SpoofedReceive { sender: sender.to_string() }),
+    }
+}
 
+/**
+ * Buy an Offering listed in a CW20 token with the tokens sent through the `Send` hook
+ */
+fn try_receive_buy_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: BuyNft,
+) -> Result<Response, ContractError> {
     // check if offering exists
-    let off = OFFERINGS.load(deps.storage, &msg.offering_id)?;
+    let off = offerings::<String>().load(deps.storage, &msg.offering_id)?;
+    if off.expires.is_expired(&env.block) {
+        return Err(ContractError::OfferingExpired {});
+    }
+    let list_price = match &off.list_price {
+        Price::Cw20(coin) => coin,
+        Price::Native(_) => return Err(ContractError::NativePriceOnly {}),
+    };
+
+    // only the token contract of the offering can notify a payment
+    if info.sender != list_price.address {
+        return Err(ContractError::WrongToken {
+            expected: list_price.address.to_string(),
+            received: info.sender.to_string(),
+        });
+    }
 
     // chek for enough coins
-    if rcv_msg.amount < off.list_price.amount {
+    let price = off.current_price(env.block.time);
+    if rcv_msg.amount < price {
         return Err(ContractError::InsufficientFunds {});
     }
 
-    // create transfer cw20 msg to send to recipient
-    let transfer_cw20_msg = Cw20ExecuteMsg::TransferFrom {
-        owner: info.sender.clone().into_string(),
-        recipient: off.seller.clone().into_string(),
-        amount: rcv_msg.amount,
-    };
-    let exec_cw20_transfer = WasmMsg::Execute {
-        contract_addr: off.list_price.address.clone().to_string(),
-        msg: to_binary(&transfer_cw20_msg)?,
-        funds: vec![],
-    };
-    
-    // create transfer cw721 msg
-    let transfer_cw721_msg = Cw721ExecuteMsg::TransferNft {
-        recipient: rcv_msg.sender.clone(),
-        token_id: off.token_id.clone(),
-    };
-    let exec_cw721_transfer = WasmMsg::Execute {
-        contract_addr: off.contract_addr.clone().into_string(),
-        msg: to_binary(&transfer_cw721_msg)?,
-        funds: vec![],
+    let buyer = deps.api.addr_validate(&rcv_msg.sender)?;
+    settle_offering(deps, &env, msg.offering_id, off, price, buyer, rcv_msg.amount)
+}
+
+/**
+ * Buy an Offering listed in a native coin, paying with the funds attached to the message.
+ */
+pub fn try_buy_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offering_id: String,
+) -> Result<Response, ContractError> {
+    // check if offering exists
+    let off = offerings::<String>().load(deps.storage, &offering_id)?;
+    if off.expires.is_expired(&env.block) {
+        return Err(ContractError::OfferingExpired {});
+    }
+    let list_price = match &off.list_price {
+        Price::Native(coin) => coin,
+        Price::Cw20(_) => return Err(ContractError::Cw20PriceOnly {}),
     };
 
-    // if everything is fine transfer cw20 to seller
-    let cw20_transfer_cosmos_msg: CosmosMsg = exec_cw20_transfer.into();
-    // transfer nft to owner
-    let cw721_transfer_cosmos_msg: CosmosMsg = exec_cw721_transfer.into();
+    // check for enough coins
+    let paid = must_pay(&info, &list_price.denom)?;
+    let price = off.current_price(env.block.time);
+    if paid < price {
+        return Err(ContractError::InsufficientFunds {});
+    }
 
-    let cw20_submsg = SubMsg::new(cw20_transfer_cosmos_msg);
-    let cw721_submsg = SubMsg::new(cw721_transfer_cosmos_msg);
+    settle_offering(deps, &env, offering_id, off, price, info.sender, paid)
+}
 
-    let cosmos_msgs = vec![cw20_submsg, cw721_submsg];
+/**
+ * Complete the sale of an Offering once the payment is held by the marketplace:
+ * split the price between the protocol fee, the creator royalty and the seller,
+ * refund anything sent above the price and transfer the nft to the buyer.
+ * A non custodial Offering is transferred from the seller wallet with the marketplace approval
+ */
+fn settle_offering(
+    deps: DepsMut,
+    env: &Env,
+    offering_id: String,
+    off: Offering<String>,
+    price: Uint128,
+    buyer: Addr,
+    paid: Uint128,
+) -> Result<Response, ContractError> {
+    if off.reserved_for.as_ref().map_or(false, |reserved_for| *reserved_for != buyer) {
+        return Err(ContractError::ReservedForAnotherBuyer {});
+    }
+    if off.non_custodial && !listing_approved(deps.as_ref(), env, &off.contract_addr, &off.token_id, &off.seller)? {
+        return Err(ContractError::StaleListing {});
+    }
+    let payout = sale_payout(deps.as_ref(), &off.list_price, price, &off.contract_addr, &off.token_id, &off.seller)?;
 
-    // delete offering
-    OFFERINGS.remove(deps.storage, &msg.offering_id);
+    let mut cosmos_msgs = payout.messages;
+    if paid > price {
+        cosmos_msgs.push(off.list_price.transfer_msg(&buyer, paid - price)?);
+    }
+    // transfer nft to buyer
+    cosmos_msgs.push(transfer_nft_msg(&off.contract_addr, &buyer, &off.token_id)?);
 
-    let price_string = format!("{} {}", rcv_msg.amount, info.sender);
+    // delete offering and keep a record of the sale
+    offerings::<String>().remove(deps.storage, &offering_id)?;
+    record_sale(deps.storage, env, &off.contract_addr, &off.token_id, &off.seller, &buyer, off.list_price.with_amount(price))?;
 
     Ok(Response::new()
         .add_attribute("action", "buy_nft")
-        .add_attribute("buyer", rcv_msg.sender)
+        .add_attribute("buyer", buyer)
         .add_attribute("seller", off.seller)
-        .add_attribute("paid_price", price_string)
+        .add_attribute("paid_price", off.list_price.with_amount(price).to_string())
+        .add_attribute("fee", payout.fee)
+        .add_attribute("royalty", payout.royalty)
         .add_attribute("token_id", off.token_id)
         .add_attribute("contract_addr", off.contract_addr)
-        .add_submessages(cosmos_msgs)
+        .add_messages(cosmos_msgs)
     )
 }
 
+/**
+ * Payments of a sale, split between the protocol fee, the creator royalty and the seller
+ */
+pub(crate) struct SalePayout {
+    pub messages: Vec<CosmosMsg>,
+    pub fee: Uint128,
+    pub royalty: Uint128,
+}
+
+/**
+ * Split a sale of `amount` in the `currency` of a Price. The royalty is queried from the nft
+ * contract in the style of CW-2981, collections that do not implement it pay no royalty.
+ */
+pub(crate) fn sale_payout(
+    deps: Deps,
+    currency: &Price,
+    amount: Uint128,
+    contract_addr: &Addr,
+    token_id: &str,
+    seller: &Addr,
+) -> StdResult<SalePayout> {
+    let config = CONFIG.load(deps.storage)?;
+    let fee = amount.multiply_ratio(config.fee_bps, BPS_DENOMINATOR);
+
+    let royalty_query = Cw721QueryMsg::RoyaltyInfo {
+        token_id: token_id.to_string(),
+        sale_price: amount,
+    };
+    let royalty_info: Option<RoyaltiesInfoResponse> = deps
+        .querier
+        .query_wasm_smart(contract_addr, &royalty_query)
+        .ok();
+
+    let mut messages = vec![];
+    if !fee.is_zero() {
+        messages.push(currency.transfer_msg(&config.fee_recipient, fee)?);
+    }
+    let mut royalty = Uint128::zero();
+    if let Some(info) = royalty_info.filter(|r| !r.address.is_empty() && !r.royalty_amount.is_zero()) {
+        // the royalty can never take more than what is left after the fee
+        royalty = info.royalty_amount.min(amount - fee);
+        if !royalty.is_zero() {
+            messages.push(currency.transfer_msg(&deps.api.addr_validate(&info.address)?, royalty)?);
+        }
+    }
+    // zero amount transfers are refused by the chain, a royalty may leave nothing to the seller
+    let proceeds = amount - fee - royalty;
+    if !proceeds.is_zero() {
+        messages.push(currency.transfer_msg(seller, proceeds)?);
+    }
+
+    Ok(SalePayout { messages, fee, royalty })
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
+ * Handle an nft sent with `SendNft`, `info.sender` is the nft contract
  */
 pub fn try_receive_nft(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     rcv_msg: Cw721ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    let msg: SellNft = from_binary(&rcv_msg.msg)?;
+    match from_binary(&rcv_msg.msg)? {
+        ReceiveNftMsg::SellNft(msg) => try_sell_nft(deps, env, info, rcv_msg, msg),
+        ReceiveNftMsg::SellDutch(msg) => try_sell_dutch(deps, env, info, rcv_msg, msg),
+        ReceiveNftMsg::StartAuction(msg) => try_start_auction(deps, env, info, rcv_msg, msg),
+        ReceiveNftMsg::AcceptOffer(msg) => try_accept_offer(deps, env, info, rcv_msg, msg),
+        ReceiveNftMsg::AcceptCollectionOffer(msg) => try_accept_collection_offer(deps, env, info, rcv_msg, msg),
+        ReceiveNftMsg::AddToBundle(msg) => try_add_to_bundle(deps, env, info, rcv_msg, msg),
+        ReceiveNftMsg::DepositToSwap(msg) => try_deposit_to_swap(deps, info, rcv_msg, msg),
+    }
+}
 
+/**
+ * List the received nft at a fixed price
+ */
+fn try_sell_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: SellNft,
+) -> Result<Response, ContractError> {
+    let list_price = msg.list_price.validate(deps.api)?;
+    let expires = msg.expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::OfferingExpired {});
+    }
+    let reserved_for = msg.reserved_for.map(|buyer| deps.api.addr_validate(&buyer)).transpose()?;
+    save_offering(deps, env, info, rcv_msg, list_price, None, expires, reserved_for)
+}
+
+/**
+ * List the received nft with a descending price
+ */
+fn try_sell_dutch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: SellDutch,
+) -> Result<Response, ContractError> {
+    let list_price = msg.list_price.validate(deps.api)?;
+    let start_time = msg.start_time.unwrap_or(env.block.time);
+    if msg.floor_price >= list_price.amount()
+        || start_time >= msg.end_time
+        || msg.end_time <= env.block.time
+        || msg.step_interval == Some(0)
+    {
+        return Err(ContractError::InvalidPriceDecline {});
+    }
+    let price_decline = PriceDecline {
+        floor_price: msg.floor_price,
+        start_time,
+        end_time: msg.end_time,
+        step_interval: msg.step_interval,
+    };
+    save_offering(deps, env, info, rcv_msg, list_price, Some(price_decline), Expiration::Never {}, None)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_offering(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    list_price: Price,
+    price_decline: Option<PriceDecline>,
+    expires: Expiration,
+    reserved_for: Option<Addr>,
+) -> Result<Response, ContractError> {
+    check_allowed(deps.as_ref(), &info.sender, &list_price)?;
     // check same token_id from same original contract is already on sale
+    clear_previous_listing(deps.branch(), &env, &info.sender, &rcv_msg.token_id)?;
     // get OFFERING_COUNT
     let id = increment_offerings(deps.storage)?.to_string();
 
@@ -153,23 +550,61 @@ pub fn try_receive_nft(
         contract_addr: info.sender.clone(),
         token_id: rcv_msg.token_id,
         seller: deps.api.addr_validate(&rcv_msg.sender)?,
-        list_price: msg.list_price.clone(),
+        list_price,
+        price_decline,
+        expires,
+        non_custodial: false,
+        reserved_for,
         extension: format!("Offer {} from {}", token_id, deps.api.addr_validate(&rcv_msg.sender)?),
     };
 
 
-    OFFERINGS.save(deps.storage, &id, &off)?;
+    offerings::<String>().save(deps.storage, &id, &off)?;
 
-    let price_string = format!("{} {}", msg.list_price.amount, msg.list_price.address);
+    let price_string = off.list_price.to_string();
 
-    Ok(Response::new()
+    let res = Response::new()
         .add_attribute("action", "sell_nft")
         .add_attribute("original_contract", info.sender)
         .add_attribute("owner", off.owner)
         .add_attribute("seller", off.seller)
         .add_attribute("list_price", price_string)
-        .add_attribute("token_id", off.token_id)
-    )
+        .add_attribute("expires", off.expires.to_string())
+        .add_attribute("token_id", off.token_id);
+    Ok(match off.reserved_for {
+        Some(buyer) => res.add_attribute("reserved_for", buyer),
+        None => res,
+    })
+}
+
+/**
+ * Make room for a new offering of `token_id`. A token can only be listed once, in an offering or
+ * a bundle, unless its previous offering can't be bought anymore: an expired one, or a non
+ * custodial one whose owner changed or approval was revoked. Such an offering is removed
+ */
+pub(crate) fn clear_previous_listing(
+    deps: DepsMut,
+    env: &Env,
+    contract_addr: &Addr,
+    token_id: &str,
+) -> Result<(), ContractError> {
+    let previous = offerings::<String>()
+        .idx
+        .token
+        .item(deps.storage, (contract_addr.clone(), token_id.to_string()))?;
+    check_not_bundled(deps.as_ref(), env, contract_addr, token_id)?;
+    let (key, off) = match previous {
+        Some(previous) => previous,
+        None => return Ok(()),
+    };
+    let stale = off.expires.is_expired(&env.block)
+        || (off.non_custodial && !listing_approved(deps.as_ref(), env, &off.contract_addr, &off.token_id, &off.seller)?);
+    if !stale {
+        return Err(ContractError::AlreadyListed {});
+    }
+    let key = String::from_utf8(key).map_err(StdError::from)?;
+    offerings::<String>().remove(deps.storage, &key)?;
+    Ok(())
 }
 
 /**
@@ -181,34 +616,195 @@ pub fn try_withdraw(
     offering_id: String,
 ) -> Result<Response, ContractError> {
     // check if token_id is currency sold by the requesting address
-    let off = OFFERINGS.load(deps.storage, &offering_id)?;
+    let off = offerings::<String>().load(deps.storage, &offering_id)?;
     if off.seller == info.sender.clone() {
+        offerings::<String>().remove(deps.storage, &offering_id)?;
+
+        let res = Response::new()
+            .add_attribute("action", "withdraw_nft")
+            .add_attribute("seller", info.sender)
+            .add_attribute("offering_id", offering_id);
+        if off.non_custodial {
+            // the nft never left the seller wallet
+            return Ok(res);
+        }
         // transfer token back to original owner
-        let transfer_cw721_msg = Cw721ExecuteMsg::TransferNft {
-            recipient: off.seller.clone().to_string(),
-            token_id: off.token_id.clone(),
-        };
+        let cw721_submsg = SubMsg::new(transfer_nft_msg(&off.contract_addr, &off.seller, &off.token_id)?);
+        return Ok(res.add_submessage(cw721_submsg));
+    }
+    Err(ContractError::Unauthorized {})
+}
 
-        let exec_cw721_transfer = WasmMsg::Execute {
-            contract_addr: off.contract_addr.clone().into_string(),
-            msg: to_binary(&transfer_cw721_msg)?,
-            funds: vec![],
-        };
+/**
+ * Change the price of an Offering without relisting it, so it keeps its id. Only the seller
+ * can call it, a dutch listing keeps its schedule and must stay above its floor price
+ */
+pub fn try_update_price(
+    deps: DepsMut,
+    info: MessageInfo,
+    offering_id: String,
+    list_price: Price,
+) -> Result<Response, ContractError> {
+    let mut off = offerings::<String>().load(deps.storage, &offering_id)?;
+    if off.seller != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let list_price = list_price.validate(deps.api)?;
+    check_allowed(deps.as_ref(), &off.contract_addr, &list_price)?;
+    if let Some(decline) = &off.price_decline {
+        if decline.floor_price >= list_price.amount() {
+            return Err(ContractError::InvalidPriceDecline {});
+        }
+    }
+
+    let old_price = std::mem::replace(&mut off.list_price, list_price);
+    offerings::<String>().save(deps.storage, &offering_id, &off)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_price")
+        .add_event(
+            Event::new("update_price")
+                .add_attribute("offering_id", offering_id)
+                .add_attribute("old_price", old_price.to_string())
+                .add_attribute("new_price", off.list_price.to_string()),
+        )
+    )
+}
 
-        let cw721_transfer_cosmos_msg: CosmosMsg = exec_cw721_transfer.into();
-        let cw721_submsg = SubMsg::new(cw721_transfer_cosmos_msg);
+/**
+ * Return the nft of an expired Offering to its seller, anyone can call it
+ */
+pub fn try_reclaim_expired(
+    deps: DepsMut,
+    env: Env,
+    offering_id: String,
+) -> Result<Response, ContractError> {
+    let off = offerings::<String>().load(deps.storage, &offering_id)?;
+    if !off.expires.is_expired(&env.block) {
+        return Err(ContractError::OfferingNotExpired {});
+    }
+    offerings::<String>().remove(deps.storage, &offering_id)?;
 
-        OFFERINGS.remove(deps.storage, &offering_id);
+    let res = Response::new()
+        .add_attribute("action", "reclaim_expired")
+        .add_attribute("seller", off.seller.clone())
+        .add_attribute("offering_id", offering_id);
+    if off.non_custodial {
+        return Ok(res);
+    }
+    Ok(res.add_message(transfer_nft_msg(&off.contract_addr, &off.seller, &off.token_id)?))
+}
 
-        return Ok(Response::new()
-            .add_attribute("action", "withdraw_nft")
-            .add_attribute("seller", info.sender)
-            .add_attribute("offering_id", offering_id)
-            .add_submessage(cw721_submsg)
-        );
+/**
+ * Update the protocol fee settings, only the admin is allowed to call it
+ */
+pub fn try_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_bps: Option<u64>,
+    fee_recipient: Option<String>,
+    open_mode: Option<bool>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
     }
-    Err(ContractError::Unauthorized {})
+
+    if let Some(fee_bps) = fee_bps {
+        validate_fee(fee_bps)?;
+        config.fee_bps = fee_bps;
+    }
+    if let Some(recipient) = fee_recipient {
+        config.fee_recipient = deps.api.addr_validate(&recipient)?;
+    }
+    if let Some(open_mode) = open_mode {
+        config.open_mode = open_mode;
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_attribute("fee_bps", config.fee_bps.to_string())
+        .add_attribute("fee_recipient", config.fee_recipient)
+        .add_attribute("open_mode", config.open_mode.to_string())
+    )
+}
+
+/**
+ * First step of an admin transfer, the proposed admin has to accept it
+ */
+pub fn try_propose_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+    admin: String,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    let pending_admin = deps.api.addr_validate(&admin)?;
+    config.pending_admin = Some(pending_admin.clone());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_admin")
+        .add_attribute("pending_admin", pending_admin)
+    )
+}
+
+/**
+ * Second step of an admin transfer, called by the proposed admin
+ */
+pub fn try_accept_admin(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.pending_admin.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    config.admin = info.sender;
+    config.pending_admin = None;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_admin")
+        .add_attribute("admin", config.admin)
+    )
 }
+
+pub fn try_set_paused(deps: DepsMut, info: MessageInfo, paused: bool) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    config.paused = paused;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", if paused { "pause" } else { "unpause" })
+    )
+}
+
+fn validate_fee(fee_bps: u64) -> Result<(), ContractError> {
+    if fee_bps > MAX_FEE_BPS {
+        return Err(ContractError::InvalidFee { fee_bps, max_bps: MAX_FEE_BPS });
+    }
+    Ok(())
+}
+
+/**
+ * Build the message transferring `token_id` held by the marketplace to `recipient`
+ */
+pub(crate) fn transfer_nft_msg(contract_addr: &Addr, recipient: &Addr, token_id: &str) -> StdResult<CosmosMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: contract_addr.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: token_id.to_string(),
+        })?,
+        funds: vec![],
+    }
+    .into())
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  * {Deprecated}
@@ -242,12 +838,82 @@ pub fn try_reset(deps: DepsMut, info: MessageInfo, count: i32) -> Result<Respons
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(
     deps: Deps,
-    _env: Env,
+    env: Env,
     msg: QueryMsg
 ) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetCount {} => to_binary(&query_count(deps)?),
-        QueryMsg::GetOfferings {} => to_binary(&query_offerings(deps)?),
+        QueryMsg::GetOfferings { include_expired, start_after, limit } => {
+            to_binary(&query_offerings(deps, env, include_expired.unwrap_or(false), start_after, limit)?)
+        }
+        QueryMsg::Offering { offering_id } => to_binary(&query_offering(deps, env, offering_id)?),
+        QueryMsg::ReservedFor { buyer, start_after, limit } => {
+            let buyer = deps.api.addr_validate(&buyer)?;
+            let block = env.block.clone();
+            let unexpired = |off: &Offering<String>| !off.expires.is_expired(&block);
+            to_binary(&query_offerings_by_index(deps, env, &offerings().idx.reserved, buyer, unexpired, start_after, limit)?)
+        }
+        QueryMsg::OfferingsBySeller { seller, start_after, limit } => {
+            let seller = deps.api.addr_validate(&seller)?;
+            to_binary(&query_offerings_by_index(deps, env, &offerings().idx.seller, seller, is_public, start_after, limit)?)
+        }
+        QueryMsg::OfferingsByContract { contract_addr, start_after, limit } => {
+            let contract_addr = deps.api.addr_validate(&contract_addr)?;
+            to_binary(&query_offerings_by_index(deps, env, &offerings().idx.contract, contract_addr, is_public, start_after, limit)?)
+        }
+        QueryMsg::OfferingByToken { contract_addr, token_id } => {
+            to_binary(&query_offering_by_token(deps, env, contract_addr, token_id)?)
+        }
+        QueryMsg::OfferingsByPrice { contract_addr, currency, min, max, order, start_after, limit } => {
+            let order = order.unwrap_or(SortOrder::Ascending);
+            to_binary(&query_offerings_by_price(deps, env, contract_addr, currency, min, max, order, start_after, limit)?)
+        }
+        QueryMsg::FloorPrice { contract_addr, currency } => {
+            to_binary(&query_floor_price(deps, env, contract_addr, currency)?)
+        }
+        QueryMsg::OfferingsByOwner { owner, start_after, limit } => {
+            let owner = deps.api.addr_validate(&owner)?;
+            to_binary(&query_offerings_by_index(deps, env, &offerings().idx.owner, owner, is_public, start_after, limit)?)
+        }
+        QueryMsg::Config {} => to_binary(&CONFIG.load(deps.storage)?),
+        QueryMsg::Bundle { bundle_id } => to_binary(&query_bundle(deps, bundle_id)?),
+        QueryMsg::Bundles { start_after, limit } => to_binary(&query_bundles(deps, start_after, limit)?),
+        QueryMsg::Rental { rental_id } => to_binary(&query_rental(deps, env, rental_id)?),
+        QueryMsg::Rentals { start_after, limit } => to_binary(&query_rentals(deps, env, start_after, limit)?),
+        QueryMsg::NonceUsed { seller, nonce } => to_binary(&query_nonce_used(deps, seller, nonce)?),
+        QueryMsg::LazyMintCreator { contract_addr } => to_binary(&query_lazy_mint_creator(deps, contract_addr)?),
+        QueryMsg::VoucherRedeemed { voucher } => to_binary(&query_voucher_redeemed(deps, env, voucher)?),
+        QueryMsg::Swap { swap_id } => to_binary(&query_swap(deps, swap_id)?),
+        QueryMsg::Swaps { start_after, limit } => to_binary(&query_swaps(deps, start_after, limit)?),
+        QueryMsg::Trades { start_after, limit } => to_binary(&query_trades(deps, start_after, limit)?),
+        QueryMsg::TradesByCollection { contract_addr, start_after, limit } => {
+            to_binary(&query_trades_by_collection(deps, contract_addr, start_after, limit)?)
+        }
+        QueryMsg::CollectionStats { contract_addr, start_after, limit } => {
+            to_binary(&query_collection_stats(deps, contract_addr, start_after, limit)?)
+        }
+        QueryMsg::AllCollectionStats { start_after, limit } => {
+            to_binary(&query_all_collection_stats(deps, start_after, limit)?)
+        }
+        QueryMsg::AllowedCollections { start_after, limit } => {
+            to_binary(&query_allowed_collections(deps, start_after, limit)?)
+        }
+        QueryMsg::AllowedCurrencies { start_after, limit } => {
+            to_binary(&query_allowed_currencies(deps, start_after, limit)?)
+        }
+        QueryMsg::Auction { auction_id } => to_binary(&query_auction(deps, auction_id)?),
+        QueryMsg::Auctions { start_after, limit } => to_binary(&query_auctions(deps, start_after, limit)?),
+        QueryMsg::Offer { offer_id } => to_binary(&query_offer(deps, offer_id)?),
+        QueryMsg::OffersByToken { contract_addr, token_id, start_after, limit } => {
+            to_binary(&query_offers_by_token(deps, contract_addr, token_id, start_after, limit)?)
+        }
+        QueryMsg::CollectionOffer { offer_id } => to_binary(&query_collection_offer(deps, offer_id)?),
+        QueryMsg::BestCollectionOffer { contract_addr, currency } => {
+            to_binary(&query_best_collection_offer(deps, env, contract_addr, currency)?)
+        }
+        QueryMsg::OffersByBidder { bidder, start_after, limit } => {
+            to_binary(&query_offers_by_bidder(deps, bidder, start_after, limit)?)
+        }
     }
 }
 
@@ -263,33 +929,228 @@ fn query_count(deps: Deps) -> StdResult<CountResponse> {
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  */
-fn query_offerings(deps: Deps) -> StdResult<OfferingResponse> {
-    let res: StdResult<Vec<QueryOfferingResult>> = OFFERINGS
+fn query_offerings(
+    deps: Deps,
+    env: Env,
+    include_expired: bool,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OfferingResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let (page, next_start_after) = scan_page(
+        offerings::<String>().range(deps.storage, start, None, Order::Ascending),
+        |off| off.reserved_for.is_none() && (include_expired || !off.expires.is_expired(&env.block)),
+        |id, _| id.clone(),
+        limit,
+    )?;
+    let offerings = page
+        .into_iter()
+        .map(|item| parse_offering(Ok(item), env.block.time))
+        .collect::<StdResult<_>>()?;
+    Ok(OfferingResponse { offerings, next_start_after })
+}
+
+fn query_offering(deps: Deps, env: Env, offering_id: String) -> StdResult<QueryOfferingResult> {
+    let off = offerings::<String>().load(deps.storage, &offering_id)?;
+    parse_offering(Ok((offering_id, off)), env.block.time)
+}
+
+fn query_offering_by_token(
+    deps: Deps,
+    env: Env,
+    contract_addr: String,
+    token_id: String,
+) -> StdResult<OfferingByTokenResponse> {
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+    let offering = offerings::<String>()
+        .idx
+        .token
+        .item(deps.storage, (contract_addr, token_id))?
+        .filter(|(_, off)| is_public(off))
+        .map(|(key, off)| parse_offering(Ok((String::from_utf8(key)?, off)), env.block.time))
+        .transpose()?;
+    Ok(OfferingByTokenResponse { offering })
+}
+
+/**
+ * Offerings sharing `addr` in one of the OfferingIndexes and kept by `filter`, paginated by
+ * offering id
+ */
+fn query_offerings_by_index(
+    deps: Deps,
+    env: Env,
+    index: &MultiIndex<Addr, Offering<String>, String>,
+    addr: Addr,
+    filter: impl Fn(&Offering<String>) -> bool,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OfferingResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let (page, next_start_after) = scan_page(
+        index.prefix(addr).range(deps.storage, start, None, Order::Ascending),
+        filter,
+        |id, _| id.clone(),
+        limit,
+    )?;
+    let offerings = page
+        .into_iter()
+        .map(|item| parse_offering(Ok(item), env.block.time))
+        .collect::<StdResult<_>>()?;
+    Ok(OfferingResponse { offerings, next_start_after })
+}
+
+// private listings are only found through ReservedFor and their id
+fn is_public(off: &Offering<String>) -> bool {
+    off.reserved_for.is_none()
+}
+
+/**
+ * Unexpired public fixed price offerings of a collection in one currency sorted by list price,
+ * ties are broken by offering id. Dutch listings are left out, their price keeps changing
+ */
+#[allow(clippy::too_many_arguments)]
+fn query_offerings_by_price(
+    deps: Deps,
+    env: Env,
+    contract_addr: String,
+    currency: String,
+    min: Option<Uint128>,
+    max: Option<Uint128>,
+    order: SortOrder,
+    start_after: Option<(Uint128, String)>,
+    limit: Option<u32>,
+) -> StdResult<OfferingsByPriceResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+
+    // price range as bounds on (amount, offering id), the empty id sorts before any other
+    let mut low = min.map(|min| (min.u128(), String::new(), true));
+    let mut high = max.and_then(|max| max.u128().checked_add(1)).map(|above| (above, String::new(), false));
+    if let Some((amount, id)) = start_after {
+        let cursor = Some((amount.u128(), id, false));
+        match order {
+            SortOrder::Ascending if low.as_ref().map_or(true, |l| amount.u128() >= l.0) => low = cursor,
+            SortOrder::Descending if high.as_ref().map_or(true, |h| amount.u128() < h.0) => high = cursor,
+            _ => {}
+        }
+    }
+    let bound = |b: Option<(u128, String, bool)>| {
+        b.map(|(amount, id, inclusive)| match inclusive {
+            true => Bound::inclusive((amount, id)),
+            false => Bound::exclusive((amount, id)),
+        })
+    };
+
+    let order = match order {
+        SortOrder::Ascending => Order::Ascending,
+        SortOrder::Descending => Order::Descending,
+    };
+    let (page, next_start_after) = scan_page(
+        offerings::<String>()
+            .idx
+            .price
+            .sub_prefix((contract_addr, currency))
+            .range(deps.storage, bound(low), bound(high), order),
+        |off| !off.expires.is_expired(&env.block),
+        |id, off| (off.list_price.amount(), id.clone()),
+        limit,
+    )?;
+    let offerings = page
+        .into_iter()
+        .map(|item| parse_offering(Ok(item), env.block.time))
+        .collect::<StdResult<_>>()?;
+    Ok(OfferingsByPriceResponse { offerings, next_start_after })
+}
+
+// items of a page and the cursor of the next page
+type Page<K, V, C> = (Vec<(K, V)>, Option<C>);
+
+/**
+ * Read the page of at most `limit` items kept by `filter` out of `items`, scanning MAX_SCAN items
+ * at most. Unless `items` ran out, the `cursor` of the last scanned item is returned along with
+ * the page so that the next page starts after it, even when every scanned item was filtered out
+ */
+fn scan_page<K, V, C>(
+    mut items: impl Iterator<Item = StdResult<(K, V)>>,
+    filter: impl Fn(&V) -> bool,
+    cursor: impl Fn(&K, &V) -> C,
+    limit: usize,
+) -> StdResult<Page<K, V, C>> {
+    let mut page = vec![];
+    let mut last = None;
+    for _ in 0..MAX_SCAN {
+        if page.len() >= limit {
+            break;
+        }
+        match items.next() {
+            Some(item) => {
+                let (key, value) = item?;
+                last = Some(cursor(&key, &value));
+                if filter(&value) {
+                    page.push((key, value));
+                }
+            }
+            None => return Ok((page, None)),
+        }
+    }
+    Ok((page, last))
+}
+
+/**
+ * Cheapest unexpired public offering of a collection in one currency, at its current price. The
+ * cheapest fixed price offering comes from the price index, dutch listings are compared at the
+ * price they have now
+ */
+fn query_floor_price(deps: Deps, env: Env, contract_addr: String, currency: String) -> StdResult<FloorPriceResponse> {
+    let cheapest = query_offerings_by_price(deps, env.clone(), contract_addr.clone(), currency.clone(), None, None, SortOrder::Ascending, None, Some(1))?;
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+    let dutch = offerings::<String>()
+        .idx
+        .dutch
+        .prefix((contract_addr, currency))
         .range(deps.storage, None, None, Order::Ascending)
-        .map(|kv_item| parse_offering(kv_item))
-        .collect();
-    Ok(OfferingResponse {
-        offerings: res?, // Placeholder
-    })
+        .take(MAX_SCAN)
+        .filter(|item| match item {
+            Ok((_, off)) => !off.expires.is_expired(&env.block),
+            Err(_) => true,
+        })
+        .map(|item| parse_offering(item, env.block.time))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let offering = cheapest
+        .offerings
+        .into_iter()
+        .chain(dutch)
+        .min_by_key(|off| off.current_price.amount());
+    Ok(FloorPriceResponse { offering })
 }
 
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  */
-fn parse_offering<T>(item: StdResult<(String, Offering<T>)>) -> StdResult<QueryOfferingResult> 
+fn parse_offering<T>(item: StdResult<(String, Offering<T>)>, now: Timestamp) -> StdResult<QueryOfferingResult> 
 where T: Serialize + DeserializeOwned + Clone,
 {
-    item.and_then(|(k, offering)| {
+    item.map(|(k, offering)| {
         let extension = serde_json::to_string(&offering.extension).unwrap();
-        Ok(QueryOfferingResult{
-            id: k.to_string(),
-            token_id: offering.token_id,
+        QueryOfferingResult{
+            id: k,
+            token_id: offering.token_id.clone(),
+            current_price: offering.list_price.with_amount(offering.current_price(now)),
             list_price: offering.list_price,
+            price_decline: offering.price_decline,
+            expires: offering.expires,
+            non_custodial: offering.non_custodial,
+            reserved_for: offering.reserved_for.clone(),
             contract_addr: offering.contract_addr.clone(),
             seller: offering.seller.clone(),
             owner: offering.owner.clone(),
-            extension: extension,
-        })
+            extension,
+        }
     })
 }
 
@@ -299,18 +1160,78 @@ where T: Serialize + DeserializeOwned + Clone,
  * Test contracts
  */
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use cosmwasm_std::testing::{mock_dependencies,mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{Deps, DepsMut, Addr, coins, from_binary, Uint128};
+    use cosmwasm_std::testing::{mock_dependencies,mock_dependencies_with_balance, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{
+        Deps, DepsMut, Addr, BankMsg, ContractResult, OwnedDeps, SystemError, SystemResult, WasmQuery,
+        coin, coins, from_binary,
+    };
     use cw20::Cw20CoinVerified;
     use cw721::Cw721ReceiveMsg;
 
+    pub(crate) fn init_market(deps: DepsMut, fee_bps: u64) {
+        let msg = InitMsg {
+            count: 0,
+            name: "test marketplace".to_string(),
+            admin: None,
+            fee_bps,
+            fee_recipient: Some("feecollector".to_string()),
+            open_mode: None,
+        };
+        instantiate(deps, mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    // "seller" sends token_id of "nftcontract" to the marketplace
+    pub(crate) fn sell_nft(deps: DepsMut, token_id: &str, list_price: Price) -> Response {
+        let rcv_msg = HandleMsg::ReceiveNft(
+            Cw721ReceiveMsg {
+                sender: String::from("seller"),
+                token_id: token_id.to_string(),
+                msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price, expires: None, reserved_for: None })).unwrap(),
+            },
+        );
+        execute(deps, mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap()
+    }
+
+    // "cw20contract" answers as a CW20 token contract, other addresses are not contracts
+    pub(crate) fn mock_cw20(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) {
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "cw20contract" => {
+                let res = match from_binary(msg).unwrap() {
+                    Cw20QueryMsg::TokenInfo {} => to_binary(&TokenInfoResponse {
+                        name: "Token".to_string(),
+                        symbol: "TKN".to_string(),
+                        decimals: 6,
+                        total_supply: Uint128::new(1_000_000),
+                    }),
+                    _ => return SystemResult::Ok(ContractResult::Err("not supported".to_string())),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+    }
+
+    pub(crate) fn cw20_price(amount: u128) -> Price {
+        Price::Cw20(Cw20CoinVerified {
+            address: Addr::unchecked("cw20contract"),
+            amount: Uint128::new(amount),
+        })
+    }
+
     #[test]
     fn sell_offering_path() {
         let mut deps = mock_dependencies();
 
-        let msg = InitMsg { count: 17, name: "test marketplace".to_string() };
+        let msg = InitMsg {
+            count: 17,
+            name: "test marketplace".to_string(),
+            admin: None,
+            fee_bps: 0,
+            fee_recipient: None,
+            open_mode: None,
+        };
         let info = mock_info("creator", &coins(1000, "token"));
 
         // we can just call .unwrap() to assert this was a success
@@ -321,24 +1242,26 @@ mod tests {
         let info = mock_info("anyone", &coins(2, "token"));
 
         let sell_msg = SellNft {
-            list_price: Cw20CoinVerified {
-                address: Addr::unchecked("cw20ContractAddr"),
+            list_price: Price::Cw20(Cw20CoinVerified {
+                address: Addr::unchecked("cw20contract"),
                 amount: Uint128::new(5),
-            },
+            }),
+            expires: None,
+            reserved_for: None,
         };
 
         let rcv_msg = HandleMsg::ReceiveNft(
             Cw721ReceiveMsg {
                 sender: String::from("seller"),
                 token_id: String::from("SellableNFT"),
-                msg: to_binary(&sell_msg).unwrap(),
+                msg: to_binary(&ReceiveNftMsg::SellNft(sell_msg)).unwrap(),
             },
         );
         
         let _res = execute(deps.as_mut(), mock_env(), info, rcv_msg).unwrap();
 
         // Offering should be listed
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
         let value: OfferingResponse = from_binary(&res).unwrap();
         assert_eq!(1, value.offerings.len());
 
@@ -350,16 +1273,16 @@ mod tests {
             Cw20ReceiveMsg {
                 sender: String::from("buyer"),
                 amount: Uint128::new(5),
-                msg: to_binary(&buy_msg).unwrap()
+                msg: to_binary(&ReceiveMsg::BuyNft(buy_msg)).unwrap()
             }
         );
 
-        let info_buy = mock_info("cw20ContractAddr", &coins(2, "token"));
+        let info_buy = mock_info("cw20contract", &coins(2, "token"));
 
         let _res = execute(deps.as_mut(), mock_env(), info_buy, rcv_msg).unwrap();
 
         // check Offerings again. Should be 0
-        let buy_res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings {}).unwrap();
+        let buy_res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
         let buy_value: OfferingResponse = from_binary(&buy_res).unwrap();
         assert_eq!(0, buy_value.offerings.len());
     }
@@ -371,6 +1294,10 @@ mod tests {
         let msg = InitMsg {
             name: String::from("test market"),
             count: 1000,
+            admin: None,
+            fee_bps: 0,
+            fee_recipient: None,
+            open_mode: None,
         };
         let info = mock_info("creator", &coins(2, "token"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -379,24 +1306,26 @@ mod tests {
         let info = mock_info("anyone", &coins(2, "token"));
 
         let sell_msg = SellNft {
-            list_price: Cw20CoinVerified {
-                address: Addr::unchecked("cw20ContractAddr"),
+            list_price: Price::Cw20(Cw20CoinVerified {
+                address: Addr::unchecked("cw20contract"),
                 amount: Uint128::new(5),
-            },
+            }),
+            expires: None,
+            reserved_for: None,
         };
 
         let rcv_msg = HandleMsg::ReceiveNft(
             Cw721ReceiveMsg {
                 sender: String::from("seller"),
                 token_id: String::from("SellableNFT"),
-                msg: to_binary(&sell_msg).unwrap(),
+                msg: to_binary(&ReceiveNftMsg::SellNft(sell_msg)).unwrap(),
             },
         );
 
         let _res = execute(deps.as_mut(), mock_env(), info, rcv_msg).unwrap();
 
         // Offerings should be listed
-        let list_res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings {}).unwrap();
+        let list_res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
         let list_value: OfferingResponse = from_binary(&list_res).unwrap();
         assert_eq!(1, list_value.offerings.len());
         //assert_eq!("1", list_value.offerings[0].id.clone());
@@ -408,8 +1337,770 @@ mod tests {
         let _res = execute(deps.as_mut(), mock_env(), withraw_info, withraw_msg).unwrap();
         assert_eq!("1", _res.attributes[2].value);
         // Offering should be removed
-        let rm_res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings {}).unwrap();
+        let rm_res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
         let rm_value: OfferingResponse = from_binary(&rm_res).unwrap();
         assert_eq!(0, rm_value.offerings.len());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn buy_native_offering_path() {
+        let mut deps = mock_dependencies();
+
+        init_market(deps.as_mut(), 0);
+
+        // seller lists the nft for a native coin
+        sell_nft(deps.as_mut(), "SellableNFT", Price::Native(coin(100, "ujuno")));
+
+        // native offering can not be bought through a cw20 hook
+        let rcv_msg = HandleMsg::Receive(
+            Cw20ReceiveMsg {
+                sender: String::from("buyer"),
+                amount: Uint128::new(100),
+                msg: to_binary(&ReceiveMsg::BuyNft(BuyNft { offering_id: "1".to_string() })).unwrap()
+            }
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), rcv_msg).unwrap_err();
+        assert!(matches!(err, ContractError::NativePriceOnly {}));
+
+        // wrong denom and not enough coins are rejected
+        let buy_msg = HandleMsg::BuyNft { offering_id: "1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "uatom")), buy_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(_)));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(99, "ujuno")), buy_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+
+        // overpayment is refunded to the buyer
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(120, "ujuno")), buy_msg).unwrap();
+        assert_eq!(3, res.messages.len());
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(100, "ujuno") })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "buyer".to_string(), amount: coins(20, "ujuno") })
+        );
+
+        // offering is removed after the sale
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
+        let value: OfferingResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.offerings.len());
+    }
+
+    #[test]
+    fn cw20_receive_must_come_from_listed_token() {
+        let mut deps = mock_dependencies();
+
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "SellableNFT", cw20_price(5));
+
+        let rcv_msg = HandleMsg::Receive(
+            Cw20ReceiveMsg {
+                sender: String::from("buyer"),
+                amount: Uint128::new(7),
+                msg: to_binary(&ReceiveMsg::BuyNft(BuyNft { offering_id: "1".to_string() })).unwrap()
+            }
+        );
+
+        // a spoofed receive sent directly by the buyer is rejected
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &[]), rcv_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+        // as is a payment in another cw20 token
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), rcv_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+
+        // the listed token pays the seller from the tokens sent to the marketplace
+        let res = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), rcv_msg).unwrap();
+        let transfer = |recipient: &str, amount: u128| -> CosmosMsg {
+            WasmMsg::Execute {
+                contract_addr: "cw20contract".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::new(amount),
+                }).unwrap(),
+                funds: vec![],
+            }.into()
+        };
+        assert_eq!(3, res.messages.len());
+        assert_eq!(res.messages[0].msg, transfer("seller", 5));
+        assert_eq!(res.messages[1].msg, transfer("buyer", 2));
+    }
+
+    #[test]
+    fn spoofed_receive_rejected() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_cw20(&mut deps);
+
+        let offer = crate::msg::PlaceOffer { contract_addr: String::from("nftcontract"), token_id: String::from("Unlisted"), expires: None };
+        let rcv_msg = |msg: Binary| HandleMsg::Receive(Cw20ReceiveMsg { sender: String::from("mallory"), amount: Uint128::new(1_000), msg });
+
+        // an account can't escrow a made-up balance of itself by calling Receive directly
+        let place = to_binary(&ReceiveMsg::PlaceOffer(offer)).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("mallory", &[]), rcv_msg(place.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::SpoofedReceive { sender } if sender == "mallory"));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), rcv_msg(Binary::default())).unwrap_err();
+        assert!(matches!(err, ContractError::NoData {}));
+
+        execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), rcv_msg(place)).unwrap();
+    }
+
+    #[test]
+    fn protocol_fee_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 250);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let config: Config = from_binary(&res).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                admin: Addr::unchecked("creator"),
+                fee_bps: 250,
+                fee_recipient: Addr::unchecked("feecollector"),
+                open_mode: true,
+                pending_admin: None,
+                paused: false,
+            }
+        );
+
+        // the sale is split between the fee collector and the seller
+        sell_nft(deps.as_mut(), "SellableNFT", Price::Native(coin(1000, "ujuno")));
+        let buy_msg = HandleMsg::BuyNft { offering_id: "1".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1000, "ujuno")), buy_msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "feecollector".to_string(), amount: coins(25, "ujuno") })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(975, "ujuno") })
+        );
+
+        // only the admin can update the config, within the fee limit
+        let update_msg = HandleMsg::UpdateConfig { fee_bps: Some(0), fee_recipient: None, open_mode: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), update_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let invalid_msg = HandleMsg::UpdateConfig { fee_bps: Some(MAX_FEE_BPS + 1), fee_recipient: None, open_mode: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), invalid_msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { .. }));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), update_msg).unwrap();
+
+        // without a fee the seller gets the whole price
+        sell_nft(deps.as_mut(), "OtherNFT", Price::Native(coin(1000, "ujuno")));
+        let buy_msg = HandleMsg::BuyNft { offering_id: "2".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1000, "ujuno")), buy_msg).unwrap();
+        assert_eq!(2, res.messages.len());
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(1000, "ujuno") })
+        );
+    }
+
+    #[test]
+    fn royalty_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 100);
+
+        // nftcontract asks a 10% royalty for the creator
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "nftcontract" => {
+                match from_binary(msg).unwrap() {
+                    Cw721QueryMsg::RoyaltyInfo { sale_price, .. } => SystemResult::Ok(ContractResult::Ok(
+                        to_binary(&RoyaltiesInfoResponse {
+                            address: "creator".to_string(),
+                            royalty_amount: sale_price.multiply_ratio(10u64, 100u64),
+                        }).unwrap(),
+                    )),
+                    _ => panic!("unexpected query"),
+                }
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+
+        sell_nft(deps.as_mut(), "SellableNFT", Price::Native(coin(1000, "ujuno")));
+        let buy_msg = HandleMsg::BuyNft { offering_id: "1".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1000, "ujuno")), buy_msg).unwrap();
+
+        // fee first, then the creator royalty and the rest to the seller
+        let expected: Vec<CosmosMsg> = vec![
+            BankMsg::Send { to_address: "feecollector".to_string(), amount: coins(10, "ujuno") }.into(),
+            BankMsg::Send { to_address: "creator".to_string(), amount: coins(100, "ujuno") }.into(),
+            BankMsg::Send { to_address: "seller".to_string(), amount: coins(890, "ujuno") }.into(),
+        ];
+        let sent: Vec<CosmosMsg> = res.messages.iter().take(3).map(|m| m.msg.clone()).collect();
+        assert_eq!(expected, sent);
+    }
+
+    #[test]
+    fn full_royalty_skips_the_seller() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), MAX_FEE_BPS);
+
+        // the fee can't take the whole sale
+        let full_fee = HandleMsg::UpdateConfig { fee_bps: Some(BPS_DENOMINATOR), fee_recipient: None, open_mode: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), full_fee).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { fee_bps: 10_000, max_bps: 1_000 }));
+
+        // nftcontract asks the whole sale price as royalty
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "nftcontract" => {
+                match from_binary(msg).unwrap() {
+                    Cw721QueryMsg::RoyaltyInfo { sale_price, .. } => SystemResult::Ok(ContractResult::Ok(
+                        to_binary(&RoyaltiesInfoResponse { address: "creator".to_string(), royalty_amount: sale_price }).unwrap(),
+                    )),
+                    _ => panic!("unexpected query"),
+                }
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+
+        // the royalty gets what the fee leaves and no empty transfer is sent to the seller
+        sell_nft(deps.as_mut(), "SellableNFT", Price::Native(coin(1000, "ujuno")));
+        let buy_msg = HandleMsg::BuyNft { offering_id: "1".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1000, "ujuno")), buy_msg).unwrap();
+        let expected: Vec<CosmosMsg> = vec![
+            BankMsg::Send { to_address: "feecollector".to_string(), amount: coins(100, "ujuno") }.into(),
+            BankMsg::Send { to_address: "creator".to_string(), amount: coins(900, "ujuno") }.into(),
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "SellableNFT").unwrap(),
+        ];
+        let sent: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(expected, sent);
+    }
+
+    #[test]
+    fn dutch_offering_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        // price falls from 1000 to 400 over 600 seconds, by steps of 100 seconds
+        let start = mock_env().block.time;
+        let sell_msg = SellDutch {
+            list_price: Price::Native(coin(1000, "ujuno")),
+            floor_price: Uint128::new(400),
+            start_time: None,
+            end_time: start.plus_seconds(600),
+            step_interval: Some(100),
+        };
+        let rcv_msg = |sell_msg: &SellDutch| HandleMsg::ReceiveNft(
+            Cw721ReceiveMsg {
+                sender: String::from("seller"),
+                token_id: String::from("DutchNFT"),
+                msg: to_binary(&ReceiveNftMsg::SellDutch(sell_msg.clone())).unwrap(),
+            },
+        );
+        let invalid = SellDutch { floor_price: Uint128::new(1000), ..sell_msg.clone() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg(&invalid)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPriceDecline {}));
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg(&sell_msg)).unwrap();
+
+        // 250 seconds in, two steps have passed
+        let mut env = mock_env();
+        env.block.time = start.plus_seconds(250);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
+        let value: OfferingResponse = from_binary(&res).unwrap();
+        assert_eq!(value.offerings[0].current_price, Price::Native(coin(800, "ujuno")));
+        assert_eq!(value.offerings[0].list_price, Price::Native(coin(1000, "ujuno")));
+
+        // the sale settles at the current price and refunds the rest
+        let buy_msg = HandleMsg::BuyNft { offering_id: "1".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("buyer", &coins(799, "ujuno")), buy_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = execute(deps.as_mut(), env, mock_info("buyer", &coins(1000, "ujuno")), buy_msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(800, "ujuno") })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "buyer".to_string(), amount: coins(200, "ujuno") })
+        );
+    }
+
+    #[test]
+    fn expired_offering_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(60));
+        let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("ExpiringNFT"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft {
+                list_price: Price::Native(coin(100, "ujuno")),
+                expires: Some(expires),
+                reserved_for: None,
+            }))
+            .unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap();
+
+        // can't be reclaimed while it is still for sale
+        let reclaim = HandleMsg::ReclaimExpired { offering_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), reclaim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::OfferingNotExpired {}));
+
+        // expired offerings can't be bought and are hidden unless asked for
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(60);
+        let buy = HandleMsg::BuyNft { offering_id: String::from("1") };
+        let err = execute(deps.as_mut(), later.clone(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap_err();
+        assert!(matches!(err, ContractError::OfferingExpired {}));
+
+        let res = query(deps.as_ref(), later.clone(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
+        let value: OfferingResponse = from_binary(&res).unwrap();
+        assert!(value.offerings.is_empty());
+        let res = query(deps.as_ref(), later.clone(), QueryMsg::GetOfferings { include_expired: Some(true), start_after: None, limit: None }).unwrap();
+        let value: OfferingResponse = from_binary(&res).unwrap();
+        assert_eq!(value.offerings[0].expires, expires);
+
+        // anyone can send the nft back to the seller
+        let res = execute(deps.as_mut(), later, mock_info("anyone", &[]), reclaim).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("seller"), "ExpiringNFT").unwrap()
+        );
+        assert!(offerings::<String>().may_load(deps.as_ref().storage, "1").unwrap().is_none());
+    }
+
+    #[test]
+    fn update_price_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "RepricedNFT", Price::Native(coin(100, "ujuno")));
+
+        let update = HandleMsg::UpdatePrice {
+            offering_id: String::from("1"),
+            list_price: Price::Native(coin(80, "ujuno")),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), update.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // the event records both prices for indexers
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), update).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("update_price")
+                .add_attribute("offering_id", "1")
+                .add_attribute("old_price", "100 ujuno")
+                .add_attribute("new_price", "80 ujuno")]
+        );
+
+        // the offering keeps its id and sells at the new price
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None }).unwrap();
+        let value: OfferingResponse = from_binary(&res).unwrap();
+        assert_eq!(value.offerings[0].id, "1");
+        assert_eq!(value.offerings[0].list_price, Price::Native(coin(80, "ujuno")));
+        let buy = HandleMsg::BuyNft { offering_id: String::from("1") };
+        execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(80, "ujuno")), buy).unwrap();
+    }
+
+    #[test]
+    fn paginated_offering_queries() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        for token_id in ["NFT1", "NFT2", "NFT3"] {
+            sell_nft(deps.as_mut(), token_id, Price::Native(coin(100, "ujuno")));
+        }
+
+        let by_seller = |start_after: Option<&str>| QueryMsg::OfferingsBySeller {
+            seller: String::from("seller"),
+            start_after: start_after.map(String::from),
+            limit: Some(2),
+        };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_seller(None)).unwrap()).unwrap();
+        let ids: Vec<_> = res.offerings.iter().map(|o| o.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_seller(Some("2"))).unwrap()).unwrap();
+        assert_eq!(res.offerings[0].token_id, "NFT3");
+
+        // the indexes follow removals
+        let withdraw = HandleMsg::WithdrawNft { offering_id: String::from("1") };
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), withdraw).unwrap();
+        let by_contract = QueryMsg::OfferingsByContract {
+            contract_addr: String::from("nftcontract"),
+            start_after: None,
+            limit: None,
+        };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_contract).unwrap()).unwrap();
+        assert_eq!(res.offerings.len(), 2);
+
+        let by_owner = QueryMsg::OfferingsByOwner { owner: String::from("buyer"), start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_owner).unwrap()).unwrap();
+        assert!(res.offerings.is_empty());
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Offering { offering_id: String::from("2") }).unwrap();
+        let offering: QueryOfferingResult = from_binary(&res).unwrap();
+        assert_eq!(offering.token_id, "NFT2");
+    }
+
+    #[test]
+    fn offerings_by_price_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        for (token_id, amount) in [("NFT1", 300), ("NFT2", 100), ("NFT3", 200), ("NFT4", 100)] {
+            sell_nft(deps.as_mut(), token_id, Price::Native(coin(amount, "ujuno")));
+        }
+        sell_nft(deps.as_mut(), "NFT5", cw20_price(50));
+
+        let by_price = |min: Option<u128>, max: Option<u128>, order: SortOrder, start_after: Option<(u128, &str)>| {
+            let msg = QueryMsg::OfferingsByPrice {
+                contract_addr: String::from("nftcontract"),
+                currency: String::from("ujuno"),
+                min: min.map(Uint128::new),
+                max: max.map(Uint128::new),
+                order: Some(order),
+                start_after: start_after.map(|(amount, id)| (Uint128::new(amount), String::from(id))),
+                limit: Some(2),
+            };
+            let res: OfferingsByPriceResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            res.offerings.into_iter().map(|o| o.id).collect::<Vec<_>>()
+        };
+
+        // cheapest first, ties by offering id, paginated on (price, id)
+        assert_eq!(by_price(None, None, SortOrder::Ascending, None), vec!["2", "4"]);
+        assert_eq!(by_price(None, None, SortOrder::Ascending, Some((100, "4"))), vec!["3", "1"]);
+        assert_eq!(by_price(None, None, SortOrder::Descending, None), vec!["1", "3"]);
+        assert_eq!(by_price(Some(150), Some(300), SortOrder::Descending, Some((300, "1"))), vec!["3"]);
+        assert_eq!(by_price(Some(100), Some(100), SortOrder::Ascending, Some((100, "2"))), vec!["4"]);
+
+        let floor = |currency: &str| {
+            let msg = QueryMsg::FloorPrice { contract_addr: String::from("nftcontract"), currency: String::from(currency) };
+            let res: FloorPriceResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            res.offering.map(|o| o.list_price)
+        };
+        assert_eq!(floor("ujuno"), Some(Price::Native(coin(100, "ujuno"))));
+        assert_eq!(floor("cw20contract"), Some(cw20_price(50)));
+        assert_eq!(floor("uatom"), None);
+    }
+
+    #[test]
+    fn price_pages_skip_dutch_expired_and_reserved() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let start = mock_env().block.time;
+        let sell = |token_id: &str, msg: ReceiveNftMsg| HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: token_id.to_string(),
+            msg: to_binary(&msg).unwrap(),
+        });
+        let fixed = |amount: u128, expires: Option<Expiration>, reserved_for: Option<&str>| ReceiveNftMsg::SellNft(SellNft {
+            list_price: Price::Native(coin(amount, "ujuno")),
+            expires,
+            reserved_for: reserved_for.map(String::from),
+        });
+        let dutch = ReceiveNftMsg::SellDutch(SellDutch {
+            list_price: Price::Native(coin(1000, "ujuno")),
+            floor_price: Uint128::new(40),
+            start_time: None,
+            end_time: start.plus_seconds(600),
+            step_interval: None,
+        });
+        let nft = mock_info("nftcontract", &[]);
+        execute(deps.as_mut(), mock_env(), nft.clone(), sell("Fixed", fixed(100, None, None))).unwrap();
+        execute(deps.as_mut(), mock_env(), nft.clone(), sell("Expiring", fixed(50, Some(Expiration::AtTime(start.plus_seconds(60))), None))).unwrap();
+        execute(deps.as_mut(), mock_env(), nft.clone(), sell("Private", fixed(20, None, Some("friend")))).unwrap();
+        execute(deps.as_mut(), mock_env(), nft, sell("Dutch", dutch)).unwrap();
+
+        let by_price = |env: Env| {
+            let msg = QueryMsg::OfferingsByPrice {
+                contract_addr: String::from("nftcontract"),
+                currency: String::from("ujuno"),
+                min: None,
+                max: None,
+                order: None,
+                start_after: None,
+                limit: None,
+            };
+            let res: OfferingsByPriceResponse = from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+            res.offerings.into_iter().map(|o| o.id).collect::<Vec<_>>()
+        };
+        let floor = |env: Env| {
+            let msg = QueryMsg::FloorPrice { contract_addr: String::from("nftcontract"), currency: String::from("ujuno") };
+            let res: FloorPriceResponse = from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+            res.offering.map(|o| (o.id, o.current_price))
+        };
+
+        // the private and the dutch listings are never in price pages
+        assert_eq!(by_price(mock_env()), vec!["2", "1"]);
+        assert_eq!(floor(mock_env()), Some((String::from("2"), Price::Native(coin(50, "ujuno")))));
+
+        // once the cheap listing expired and the dutch price fell, the dutch listing is the floor
+        let mut later = mock_env();
+        later.block.time = start.plus_seconds(600);
+        assert_eq!(by_price(later.clone()), vec!["1"]);
+        assert_eq!(floor(later), Some((String::from("4"), Price::Native(coin(40, "ujuno")))));
+    }
+
+    #[test]
+    fn pages_continue_past_hidden_offerings() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        // offering "99" comes last in id order, after MAX_SCAN offerings that will expire
+        for id in 1..=MAX_SCAN + 1 {
+            let expires = Some(Expiration::AtHeight(mock_env().block.height + 1)).filter(|_| id != 99);
+            let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: String::from("seller"),
+                token_id: format!("NFT{}", id),
+                msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price: Price::Native(coin(100, "ujuno")), expires, reserved_for: None })).unwrap(),
+            });
+            execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap();
+        }
+        let mut later = mock_env();
+        later.block.height += 1;
+
+        // a page of expired offerings only is empty but still points to the next one
+        let get_offerings = |start_after: Option<String>| {
+            let msg = QueryMsg::GetOfferings { include_expired: None, start_after, limit: None };
+            let res: OfferingResponse = from_binary(&query(deps.as_ref(), later.clone(), msg).unwrap()).unwrap();
+            res
+        };
+        let first = get_offerings(None);
+        assert!(first.offerings.is_empty() && first.next_start_after.is_some());
+        let (mut ids, mut next) = (vec![], first.next_start_after);
+        while next.is_some() {
+            let page = get_offerings(next);
+            ids.extend(page.offerings.into_iter().map(|o| o.id));
+            next = page.next_start_after;
+        }
+        assert_eq!(ids, vec!["99"]);
+
+        // price pages are walked the same way
+        let by_price = |start_after: Option<(Uint128, String)>| {
+            let msg = QueryMsg::OfferingsByPrice {
+                contract_addr: String::from("nftcontract"),
+                currency: String::from("ujuno"),
+                min: None,
+                max: None,
+                order: None,
+                start_after,
+                limit: None,
+            };
+            let res: OfferingsByPriceResponse = from_binary(&query(deps.as_ref(), later.clone(), msg).unwrap()).unwrap();
+            res
+        };
+        let (mut ids, mut next) = (vec![], by_price(None).next_start_after);
+        while next.is_some() {
+            let page = by_price(next);
+            ids.extend(page.offerings.into_iter().map(|o| o.id));
+            next = page.next_start_after;
+        }
+        assert_eq!(ids, vec!["99"]);
+    }
+
+    #[test]
+    fn duplicate_listing_rejected() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "OnceNFT", Price::Native(coin(100, "ujuno")));
+
+        let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("OnceNFT"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price: cw20_price(5), expires: None, reserved_for: None })).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyListed {}));
+
+        let by_token = |token_id: &str| {
+            let msg = QueryMsg::OfferingByToken { contract_addr: String::from("nftcontract"), token_id: String::from(token_id) };
+            let res: OfferingByTokenResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            res.offering
+        };
+        assert_eq!(by_token("OnceNFT").unwrap().id, "1");
+        assert_eq!(by_token("OtherNFT"), None);
+    }
+
+    #[test]
+    fn private_listings_dont_blank_public_pages() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        // offering "99" is the only public one and comes last in id order, after MAX_SCAN private ones
+        for id in 1..=MAX_SCAN + 1 {
+            let reserved_for = Some(String::from("friend")).filter(|_| id != 99);
+            let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: String::from("seller"),
+                token_id: format!("NFT{}", id),
+                msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price: Price::Native(coin(100, "ujuno")), expires: None, reserved_for })).unwrap(),
+            });
+            execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap();
+        }
+
+        // a page of private offerings only is empty but still points to the next one
+        let walk = |query_msg: &dyn Fn(Option<String>) -> QueryMsg| {
+            let first: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), query_msg(None)).unwrap()).unwrap();
+            assert!(first.offerings.is_empty() && first.next_start_after.is_some());
+            let (mut ids, mut next) = (vec![], first.next_start_after);
+            while next.is_some() {
+                let page: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), query_msg(next)).unwrap()).unwrap();
+                ids.extend(page.offerings.into_iter().map(|o| o.id));
+                next = page.next_start_after;
+            }
+            ids
+        };
+        let ids = walk(&|start_after| QueryMsg::GetOfferings { include_expired: None, start_after, limit: None });
+        assert_eq!(ids, vec!["99"]);
+        let ids = walk(&|start_after| QueryMsg::OfferingsBySeller { seller: String::from("seller"), start_after, limit: None });
+        assert_eq!(ids, vec!["99"]);
+        let ids = walk(&|start_after| QueryMsg::OfferingsByContract { contract_addr: String::from("nftcontract"), start_after, limit: None });
+        assert_eq!(ids, vec!["99"]);
+    }
+
+    #[test]
+    fn reserved_listing_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "PublicNFT", Price::Native(coin(100, "ujuno")));
+        let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("OtcNFT"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft {
+                list_price: Price::Native(coin(500, "ujuno")),
+                expires: None,
+                reserved_for: Some(String::from("friend")),
+            }))
+            .unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap();
+
+        // only the public offering is listed, the private one is found through its buyer
+        let public = QueryMsg::GetOfferings { include_expired: None, start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), public).unwrap()).unwrap();
+        assert_eq!(res.offerings.iter().map(|o| o.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        let reserved = QueryMsg::ReservedFor { buyer: String::from("friend"), start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), reserved).unwrap()).unwrap();
+        assert_eq!(res.offerings.len(), 1);
+        assert_eq!(res.offerings[0].reserved_for, Some(Addr::unchecked("friend")));
+
+        // nor does it show up in the other offering queries
+        let by_seller = QueryMsg::OfferingsBySeller { seller: String::from("seller"), start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_seller).unwrap()).unwrap();
+        assert_eq!(res.offerings.iter().map(|o| o.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        let by_contract = QueryMsg::OfferingsByContract { contract_addr: String::from("nftcontract"), start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_contract).unwrap()).unwrap();
+        assert_eq!(res.offerings.iter().map(|o| o.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        let by_token = QueryMsg::OfferingByToken { contract_addr: String::from("nftcontract"), token_id: String::from("OtcNFT") };
+        let res: OfferingByTokenResponse = from_binary(&query(deps.as_ref(), mock_env(), by_token).unwrap()).unwrap();
+        assert_eq!(res.offering, None);
+
+        // an expired private offering leaves the buyer's page
+        let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("ShortNFT"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft {
+                list_price: Price::Native(coin(500, "ujuno")),
+                expires: Some(Expiration::AtHeight(mock_env().block.height + 1)),
+                reserved_for: Some(String::from("friend")),
+            }))
+            .unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap();
+        let mut later = mock_env();
+        later.block.height += 1;
+        let reserved = QueryMsg::ReservedFor { buyer: String::from("friend"), start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), later, reserved).unwrap()).unwrap();
+        assert_eq!(res.offerings.iter().map(|o| o.id.as_str()).collect::<Vec<_>>(), vec!["2"]);
+
+        let buy = HandleMsg::BuyNft { offering_id: String::from("2") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(500, "ujuno")), buy.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::ReservedForAnotherBuyer {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("friend", &coins(500, "ujuno")), buy).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("friend"), "OtcNFT").unwrap()
+        );
+    }
+
+    #[test]
+    fn admin_transfer_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        let propose = HandleMsg::ProposeAdmin { admin: String::from("newadmin") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), propose.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), propose).unwrap();
+
+        // the role only moves once the proposed admin accepts it
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), HandleMsg::AcceptAdmin {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().admin, "creator");
+        execute(deps.as_mut(), mock_env(), mock_info("newadmin", &[]), HandleMsg::AcceptAdmin {}).unwrap();
+
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!((config.admin.as_str(), config.pending_admin), ("newadmin", None));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), HandleMsg::Pause {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn pause_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "NFT1", Price::Native(coin(100, "ujuno")));
+        sell_nft(deps.as_mut(), "NFT2", Price::Native(coin(100, "ujuno")));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), HandleMsg::Pause {}).unwrap();
+
+        // no listing or sale while paused
+        let rcv_msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from("seller"),
+            token_id: String::from("NFT3"),
+            msg: to_binary(&ReceiveNftMsg::SellNft(SellNft { list_price: cw20_price(5), expires: None, reserved_for: None })).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), rcv_msg).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        let buy = HandleMsg::BuyNft { offering_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+
+        // sellers can still get their nft back
+        let withdraw = HandleMsg::WithdrawNft { offering_id: String::from("2") };
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), withdraw).unwrap();
+
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), HandleMsg::Unpause {}).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap();
+    }
+
+    #[test]
+    fn migrate_legacy_offerings() {
+        let mut deps = mock_dependencies();
+
+        // a marketplace deployed before 0.2.0: no config, offerings priced with a bare CW20 coin
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        let legacy = LegacyOffering {
+            owner: Addr::unchecked("seller"),
+            token_id: String::from("LegacyNFT"),
+            contract_addr: Addr::unchecked("nftcontract"),
+            seller: Addr::unchecked("seller"),
+            list_price: Cw20CoinVerified { address: Addr::unchecked("cw20contract"), amount: Uint128::new(5) },
+            extension: String::from("Offer LegacyNFT from seller"),
+        };
+        Map::<&str, LegacyOffering>::new("offerings").save(deps.as_mut().storage, "1", &legacy).unwrap();
+        OFFERINGS_COUNT.save(deps.as_mut().storage, &1).unwrap();
+
+        let msg = MigrateMsg { admin: None, fee_bps: None, fee_recipient: None };
+        let err = migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MigrateAdminRequired {}));
+        let msg = MigrateMsg { admin: Some(String::from("creator")), fee_bps: Some(100), fee_recipient: None };
+        let res = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert!(res.attributes.contains(&attr("migrated_offerings", "1")));
+
+        // the listing is live and indexed in the current schema
+        let by_seller = QueryMsg::OfferingsBySeller { seller: String::from("seller"), start_after: None, limit: None };
+        let res: OfferingResponse = from_binary(&query(deps.as_ref(), mock_env(), by_seller).unwrap()).unwrap();
+        assert_eq!(res.offerings[0].list_price, cw20_price(5));
+        assert_eq!(res.offerings[0].extension, "\"Offer LegacyNFT from seller\"");
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().fee_recipient, "creator");
+        assert_eq!(get_contract_version(&deps.storage).unwrap().version, CONTRACT_VERSION);
+
+        // migrating again keeps the records, other contracts and downgrades are refused
+        migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert!(offerings::<String>().has(&deps.storage, "1"));
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "9.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrateVersion { .. }));
+        set_contract_version(deps.as_mut().storage, "crates.io:cw721-base", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrate { .. }));
+    }
+}
@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp, Uint128};
+use cw_utils::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,9 +13,150 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
     #[error("Insufficient funds")]
     InsufficientFunds {},
 
+    #[error("Offering is priced in a CW20 token, pay through the token Send hook")]
+    Cw20PriceOnly {},
+
+    #[error("Offering is priced in a native coin, pay with BuyNft and attached funds")]
+    NativePriceOnly {},
+
+    #[error("Offering is priced in CW20 {expected}, payment received from {received}")]
+    WrongToken { expected: String, received: String },
+
+    #[error("Receive called by {sender}, which is not a CW20 token contract")]
+    SpoofedReceive { sender: String },
+
+    #[error("Dutch listing must decline from its list price to a lower floor over a future time window")]
+    InvalidPriceDecline {},
+
+    #[error("Auction end time must be in the future")]
+    InvalidEndTime {},
+
+    #[error("Auction has ended")]
+    AuctionEnded {},
+
+    #[error("Auction is still running")]
+    AuctionNotEnded {},
+
+    #[error("Bid is too low, minimum bid is {min_bid}")]
+    BidTooLow { min_bid: Uint128 },
+
+    #[error("Collection {contract_addr} is not allowed on the marketplace")]
+    CollectionNotAllowed { contract_addr: String },
+
+    #[error("Currency {currency} is not accepted on the marketplace")]
+    CurrencyNotAllowed { currency: String },
+
+    #[error("Cannot migrate from contract {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from version {previous_version}")]
+    CannotMigrateVersion { previous_version: String },
+
+    #[error("An admin is required to create the config while migrating")]
+    MigrateAdminRequired {},
+
+    #[error("Bundle is already for sale")]
+    BundlePublished {},
+
+    #[error("Bundle is not for sale")]
+    BundleNotPublished {},
+
+    #[error("Bundle has no nft")]
+    EmptyBundle {},
+
+    #[error("Bundle can't hold more than {max} nfts")]
+    BundleFull { max: usize },
+
+    #[error("Bundle price must be at least {min}, one unit for each of its nfts")]
+    BundlePriceTooLow { min: Uint128 },
+
+    #[error("Rental period, maximum number of periods and price must be positive")]
+    InvalidRental {},
+
+    #[error("Rent between 1 and {max} periods")]
+    InvalidPeriods { max: u32 },
+
+    #[error("Nft is rented until {until}")]
+    AlreadyRented { until: Timestamp },
+
+    #[error("Order signature does not match the seller")]
+    InvalidSignature {},
+
+    #[error("Nonce {nonce} of the seller was already used")]
+    NonceUsed { nonce: u64 },
+
+    #[error("Collection has no lazy mint creator")]
+    NoLazyMintCreator {},
+
+    #[error("Voucher has expired")]
+    VoucherExpired {},
+
+    #[error("Voucher was already redeemed")]
+    VoucherRedeemed {},
+
+    #[error("A swap needs between 1 and {max} nfts on each side")]
+    InvalidSwap { max: usize },
+
+    #[error("A swap can't be made with its own maker")]
+    SelfSwap {},
+
+    #[error("Nft {token_id} of {contract_addr} is more than once in the swap")]
+    DuplicateSwapItem { contract_addr: String, token_id: String },
+
+    #[error("Nft is not part of the swap")]
+    NotInSwap {},
+
+    #[error("A sweep must buy between 1 and {max} distinct offerings")]
+    InvalidSweep { max: usize },
+
+    #[error("Offerings of a sweep must be priced in the same currency")]
+    CurrencyMismatch {},
+
+    #[error("Total price {total} exceeds the maximum of {max_total}")]
+    MaxTotalExceeded { total: Uint128, max_total: Uint128 },
+
+    #[error("Marketplace is paused")]
+    Paused {},
+
+    #[error("Nft is already listed")]
+    AlreadyListed {},
+
+    #[error("Marketplace is not approved to transfer the nft")]
+    NotApproved {},
+
+    #[error("Seller no longer owns the nft or revoked the marketplace approval")]
+    StaleListing {},
+
+    #[error("Listing is still valid")]
+    ListingNotStale {},
+
+    #[error("Offering is reserved for another buyer")]
+    ReservedForAnotherBuyer {},
+
+    #[error("Offering has expired")]
+    OfferingExpired {},
+
+    #[error("Offering has not expired yet")]
+    OfferingNotExpired {},
+
+    #[error("Offer has expired")]
+    OfferExpired {},
+
+    #[error("Quantity must be positive and divide the escrowed amount")]
+    InvalidQuantity {},
+
+    #[error("Offer was made on another nft")]
+    OfferTokenMismatch {},
+
+    #[error("Fee of {fee_bps} bps exceeds the maximum of {max_bps} bps")]
+    InvalidFee { fee_bps: u64, max_bps: u64 },
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
     // Add any other custom errors you like here.
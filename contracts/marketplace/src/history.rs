@@ -0,0 +1,218 @@
+use cosmwasm_std::{Addr, Deps, Env, Order, StdResult, Storage};
+use cw_storage_plus::Bound;
+
+use crate::contract::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::package::{CollectionStatsResponse, Price, QueryCollectionStatsResult, QueryTradeResult, TradesResponse};
+use crate::state::{increment_trades, trades, CollectionStats, Trade, COLLECTION_STATS};
+
+/**
+ * Record a completed sale of `price` and add it to the stats of the collection in the sale
+ * currency. Called by every path that sells an nft, returns the trade_id
+ */
+pub(crate) fn record_sale(
+    storage: &mut dyn Storage,
+    env: &Env,
+    contract_addr: &Addr,
+    token_id: &str,
+    seller: &Addr,
+    buyer: &Addr,
+    price: Price,
+) -> StdResult<u64> {
+    let amount = price.amount();
+    let currency = price.currency();
+    COLLECTION_STATS.update(storage, (contract_addr, &currency), |stats| -> StdResult<_> {
+        let mut stats: CollectionStats = stats.unwrap_or_default();
+        stats.volume += amount;
+        stats.sales += 1;
+        stats.last_sale = amount;
+        stats.highest_sale = stats.highest_sale.max(amount);
+        Ok(stats)
+    })?;
+
+    let id = increment_trades(storage)?;
+    let trade = Trade {
+        contract_addr: contract_addr.clone(),
+        token_id: token_id.to_string(),
+        seller: seller.clone(),
+        buyer: buyer.clone(),
+        price,
+        time: env.block.time,
+    };
+    trades().save(storage, id, &trade)?;
+    Ok(id)
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_trades(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> StdResult<TradesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let trades = trades()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, trade)| parse_trade(id, trade)))
+        .collect::<StdResult<_>>()?;
+    Ok(TradesResponse { trades })
+}
+
+pub fn query_trades_by_collection(
+    deps: Deps,
+    contract_addr: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TradesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+
+    let trades = trades()
+        .idx
+        .contract
+        .prefix(contract_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, trade)| parse_trade(id, trade)))
+        .collect::<StdResult<_>>()?;
+    Ok(TradesResponse { trades })
+}
+
+pub fn query_collection_stats(
+    deps: Deps,
+    contract_addr: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<CollectionStatsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+
+    let stats = COLLECTION_STATS
+        .prefix(&contract_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(currency, stats)| parse_stats(contract_addr.clone(), currency, stats)))
+        .collect::<StdResult<_>>()?;
+    Ok(CollectionStatsResponse { stats })
+}
+
+pub fn query_all_collection_stats(
+    deps: Deps,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> StdResult<CollectionStatsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|(contract_addr, currency)| deps.api.addr_validate(&contract_addr).map(|addr| (addr, currency)))
+        .transpose()?;
+    let start = start_after
+        .as_ref()
+        .map(|(contract_addr, currency)| Bound::exclusive((contract_addr, currency.as_str())));
+
+    let stats = COLLECTION_STATS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|((contract_addr, currency), stats)| parse_stats(contract_addr, currency, stats)))
+        .collect::<StdResult<_>>()?;
+    Ok(CollectionStatsResponse { stats })
+}
+
+fn parse_trade(id: u64, trade: Trade) -> QueryTradeResult {
+    QueryTradeResult {
+        id,
+        contract_addr: trade.contract_addr,
+        token_id: trade.token_id,
+        seller: trade.seller,
+        buyer: trade.buyer,
+        price: trade.price,
+        time: trade.time,
+    }
+}
+
+fn parse_stats(contract_addr: Addr, currency: String, stats: CollectionStats) -> QueryCollectionStatsResult {
+    QueryCollectionStatsResult {
+        contract_addr,
+        currency,
+        volume: stats.volume,
+        sales: stats.sales,
+        last_sale: stats.last_sale,
+        highest_sale: stats.highest_sale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market, sell_nft};
+    use crate::contract::{execute, query};
+    use crate::msg::{BuyMany, BuyNft, HandleMsg, QueryMsg, ReceiveMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, Uint128};
+    use cw20::Cw20ReceiveMsg;
+
+    #[test]
+    fn trade_history_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "NFT1", Price::Native(coin(100, "ujuno")));
+        sell_nft(deps.as_mut(), "NFT2", Price::Native(coin(150, "ujuno")));
+        sell_nft(deps.as_mut(), "NFT3", cw20_price(10));
+
+        // a plain buy, a sweep and a cw20 buy are all recorded
+        let buy = HandleMsg::BuyNft { offering_id: String::from("1") };
+        execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap();
+        let sweep = HandleMsg::BuyMany(BuyMany { offering_ids: vec![String::from("2")], max_total: Uint128::new(150) });
+        execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(150, "ujuno")), sweep).unwrap();
+        let receive = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("buyer"),
+            amount: Uint128::new(10),
+            msg: to_binary(&ReceiveMsg::BuyNft(BuyNft { offering_id: String::from("3") })).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive).unwrap();
+
+        let msg = QueryMsg::TradesByCollection { contract_addr: String::from("nftcontract"), start_after: Some(1), limit: None };
+        let res: TradesResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.trades.len(), 2);
+        assert_eq!(res.trades[0].token_id, "NFT2");
+        assert_eq!(res.trades[0].seller, "seller");
+        assert_eq!(res.trades[0].buyer, "buyer");
+        assert_eq!(res.trades[0].price, Price::Native(coin(150, "ujuno")));
+        assert_eq!(res.trades[0].time, mock_env().block.time);
+
+        // stats are kept per currency
+        let msg = QueryMsg::CollectionStats { contract_addr: String::from("nftcontract"), start_after: None, limit: None };
+        let res: CollectionStatsResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.stats.len(), 2);
+        assert_eq!(res.stats[0].currency, "cw20contract");
+        assert_eq!(res.stats[0].sales, 1);
+        let ujuno = &res.stats[1];
+        assert_eq!(
+            (ujuno.volume, ujuno.sales, ujuno.last_sale, ujuno.highest_sale),
+            (Uint128::new(250), 2, Uint128::new(150), Uint128::new(150))
+        );
+
+        let start_after = Some((String::from("nftcontract"), String::from("cw20contract")));
+        let msg = QueryMsg::AllCollectionStats { start_after, limit: None };
+        let res: CollectionStatsResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.stats, vec![ujuno.clone()]);
+    }
+
+    #[test]
+    fn trades_page_in_order() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        for id in 1..=12 {
+            sell_nft(deps.as_mut(), &format!("NFT{}", id), Price::Native(coin(100, "ujuno")));
+            let buy = HandleMsg::BuyNft { offering_id: id.to_string() };
+            execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap();
+        }
+
+        // trade 10 comes after trade 9, not after trade 1
+        let msg = QueryMsg::Trades { start_after: None, limit: Some(30) };
+        let res: TradesResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.trades.iter().map(|t| t.id).collect::<Vec<_>>(), (1..=12).collect::<Vec<_>>());
+        let msg = QueryMsg::TradesByCollection { contract_addr: String::from("nftcontract"), start_after: Some(9), limit: None };
+        let res: TradesResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(res.trades.iter().map(|t| t.token_id.as_str()).collect::<Vec<_>>(), vec!["NFT10", "NFT11", "NFT12"]);
+    }
+}
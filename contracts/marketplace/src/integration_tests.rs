@@ -39,7 +39,14 @@ mod tests {
         let mut app = mock_app();
         let cw_template_id = app.store_code(contract_template());
 
-        let msg = InitMsg { count: 1i32, name: String::from("token") };
+        let msg = InitMsg {
+            count: 1i32,
+            name: String::from("token"),
+            admin: None,
+            fee_bps: 0,
+            fee_recipient: None,
+            open_mode: None,
+        };
         let cw_template_contract_addr = app
             .instantiate_contract(
                 cw_template_id,
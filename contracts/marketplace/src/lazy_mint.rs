@@ -0,0 +1,352 @@
+use cosmwasm_std::{to_binary, Addr, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult, Uint128, WasmMsg};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::must_pay;
+use rvn_base::msg::MintMsg;
+use rvn_base::{ExecuteMsg as Cw721ExecuteMsg, Extension};
+
+use crate::allowlist::check_allowed;
+use crate::contract::sale_payout;
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::msg::{MintVoucher, RedeemVoucher};
+use crate::package::{LazyMintCreatorResponse, Price, VoucherRedeemedResponse};
+use crate::signed::{signed_digest, verify_signature};
+use crate::state::{CONFIG, LAZY_MINT_CREATORS, REDEEMED_VOUCHERS};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Set or unset the creator signing the vouchers of a collection, admin only. The marketplace
+ * must be the minter of the collection for its vouchers to be redeemed
+ */
+pub fn try_set_lazy_mint_creator(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract_addr: String,
+    creator: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+    match creator.as_deref().map(|creator| deps.api.addr_validate(creator)).transpose()? {
+        Some(creator) => LAZY_MINT_CREATORS.save(deps.storage, &contract_addr, &creator)?,
+        None => LAZY_MINT_CREATORS.remove(deps.storage, &contract_addr),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_lazy_mint_creator")
+        .add_attribute("contract_addr", contract_addr)
+        .add_attribute("creator", creator.unwrap_or_default())
+    )
+}
+
+/**
+ * Redeem a MintVoucher priced in a native coin, paying with the funds attached to the message
+ */
+pub fn try_redeem_voucher(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: RedeemVoucher,
+) -> Result<Response, ContractError> {
+    let paid = match &msg.voucher.price {
+        Price::Native(coin) => must_pay(&info, &coin.denom)?,
+        Price::Cw20(_) => return Err(ContractError::Cw20PriceOnly {}),
+    };
+    settle_voucher(deps, env, msg, info.sender, paid)
+}
+
+/**
+ * Redeem a MintVoucher priced in a CW20 token with the tokens sent through the `Send` hook
+ */
+pub fn try_receive_redeem_voucher(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: RedeemVoucher,
+) -> Result<Response, ContractError> {
+    match &msg.voucher.price {
+        Price::Cw20(coin) if coin.address != info.sender => {
+            return Err(ContractError::WrongToken {
+                expected: coin.address.to_string(),
+                received: info.sender.to_string(),
+            })
+        }
+        Price::Cw20(_) => {}
+        Price::Native(_) => return Err(ContractError::NativePriceOnly {}),
+    }
+    let buyer = deps.api.addr_validate(&rcv_msg.sender)?;
+    settle_voucher(deps, env, msg, buyer, rcv_msg.amount)
+}
+
+/**
+ * Revoke a signed MintVoucher before anyone redeems it, by the lazy mint creator of its collection.
+ * The voucher is marked as redeemed so that its signature can't be used anymore
+ */
+pub fn try_cancel_voucher(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    voucher: MintVoucher,
+) -> Result<Response, ContractError> {
+    let contract_addr = deps.api.addr_validate(&voucher.contract_addr)?;
+    let creator = LAZY_MINT_CREATORS
+        .may_load(deps.storage, &contract_addr)?
+        .ok_or(ContractError::NoLazyMintCreator {})?;
+    if info.sender != creator {
+        return Err(ContractError::Unauthorized {});
+    }
+    let digest = signed_digest(&env.contract.address, &voucher)?;
+    if REDEEMED_VOUCHERS.has(deps.storage, &digest) {
+        return Err(ContractError::VoucherRedeemed {});
+    }
+    REDEEMED_VOUCHERS.save(deps.storage, &digest, &Empty {})?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_voucher")
+        .add_attribute("creator", creator)
+        .add_attribute("token_id", voucher.token_id)
+        .add_attribute("contract_addr", contract_addr)
+    )
+}
+
+/**
+ * Mint the nft of a MintVoucher to the buyer once the payment is held by the marketplace and pay
+ * the creator like the seller of a sale. The voucher must be signed by the current lazy mint
+ * creator of the collection and not redeemed yet
+ */
+fn settle_voucher(
+    deps: DepsMut,
+    env: Env,
+    msg: RedeemVoucher,
+    buyer: Addr,
+    paid: Uint128,
+) -> Result<Response, ContractError> {
+    let voucher = &msg.voucher;
+    let contract_addr = deps.api.addr_validate(&voucher.contract_addr)?;
+    let creator = LAZY_MINT_CREATORS
+        .may_load(deps.storage, &contract_addr)?
+        .ok_or(ContractError::NoLazyMintCreator {})?;
+    if voucher.expires.is_expired(&env.block) {
+        return Err(ContractError::VoucherExpired {});
+    }
+    let digest = signed_digest(&env.contract.address, voucher)?;
+    if REDEEMED_VOUCHERS.has(deps.storage, &digest) {
+        return Err(ContractError::VoucherRedeemed {});
+    }
+    verify_signature(deps.as_ref(), &env, voucher, &msg.signature, &msg.pubkey, &creator)?;
+
+    let price = voucher.price.clone().validate(deps.api)?;
+    check_allowed(deps.as_ref(), &contract_addr, &price)?;
+    let amount = price.amount();
+    if paid < amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    REDEEMED_VOUCHERS.save(deps.storage, &digest, &Empty {})?;
+    record_sale(deps.storage, &env, &contract_addr, &voucher.token_id, &creator, &buyer, price.clone())?;
+
+    let payout = sale_payout(deps.as_ref(), &price, amount, &contract_addr, &voucher.token_id, &creator)?;
+    let mut cosmos_msgs = payout.messages;
+    if paid > amount {
+        cosmos_msgs.push(price.transfer_msg(&buyer, paid - amount)?);
+    }
+    cosmos_msgs.push(mint_msg(&contract_addr, voucher, &buyer)?);
+
+    Ok(Response::new()
+        .add_attribute("action", "redeem_voucher")
+        .add_attribute("buyer", buyer)
+        .add_attribute("creator", creator)
+        .add_attribute("paid_price", price.to_string())
+        .add_attribute("fee", payout.fee)
+        .add_attribute("royalty", payout.royalty)
+        .add_attribute("token_id", voucher.token_id.clone())
+        .add_attribute("contract_addr", contract_addr)
+        .add_messages(cosmos_msgs)
+    )
+}
+
+pub(crate) fn mint_msg(contract_addr: &Addr, voucher: &MintVoucher, owner: &Addr) -> StdResult<CosmosMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: contract_addr.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::<Extension>::Mint(MintMsg {
+            token_id: voucher.token_id.clone(),
+            owner: owner.to_string(),
+            token_uri: voucher.token_uri.clone(),
+            extension: voucher.extension.clone(),
+        }))?,
+        funds: vec![],
+    }
+    .into())
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_lazy_mint_creator(deps: Deps, contract_addr: String) -> StdResult<LazyMintCreatorResponse> {
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+    let creator = LAZY_MINT_CREATORS.may_load(deps.storage, &contract_addr)?;
+    Ok(LazyMintCreatorResponse { creator })
+}
+
+pub fn query_voucher_redeemed(deps: Deps, env: Env, voucher: MintVoucher) -> StdResult<VoucherRedeemedResponse> {
+    let digest = signed_digest(&env.contract.address, &voucher)?;
+    let redeemed = REDEEMED_VOUCHERS.has(deps.storage, &digest);
+    Ok(VoucherRedeemedResponse { redeemed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market};
+    use crate::contract::{execute, query};
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg};
+    use crate::signed::tests::{key_address, sign};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, BankMsg};
+    use cw_utils::Expiration;
+    use k256::ecdsa::SigningKey;
+
+    fn voucher(token_id: &str) -> MintVoucher {
+        MintVoucher {
+            contract_addr: String::from("lazycollection"),
+            token_id: token_id.to_string(),
+            token_uri: Some(format!("ipfs://{}", token_id)),
+            extension: None,
+            price: Price::Native(coin(50, "ujuno")),
+            expires: Expiration::AtHeight(mock_env().block.height + 100),
+        }
+    }
+
+    fn redeem(key: &SigningKey, voucher: MintVoucher) -> HandleMsg {
+        let (signature, pubkey) = sign(key, &voucher);
+        HandleMsg::RedeemVoucher(RedeemVoucher { voucher, signature, pubkey })
+    }
+
+    #[test]
+    fn lazy_mint_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let key = SigningKey::from_bytes(&[9u8; 32]).unwrap();
+        let creator = key_address(&key);
+        let funds = coins(60, "ujuno");
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), redeem(&key, voucher("Lazy1"))).unwrap_err();
+        assert!(matches!(err, ContractError::NoLazyMintCreator {}));
+        let set_creator = HandleMsg::SetLazyMintCreator {
+            contract_addr: String::from("lazycollection"),
+            creator: Some(creator.to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), set_creator.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), set_creator).unwrap();
+
+        // only the creator key signs vouchers of the collection
+        let other = SigningKey::from_bytes(&[10u8; 32]).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), redeem(&other, voucher("Lazy1"))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSignature {}));
+
+        // the creator is paid and the nft minted to the buyer
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), redeem(&key, voucher("Lazy1"))).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: creator.to_string(), amount: coins(50, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "buyer".to_string(), amount: coins(10, "ujuno") }),
+                mint_msg(&Addr::unchecked("lazycollection"), &voucher("Lazy1"), &Addr::unchecked("buyer")).unwrap(),
+            ]
+        );
+
+        // a voucher is redeemed once
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), redeem(&key, voucher("Lazy1"))).unwrap_err();
+        assert!(matches!(err, ContractError::VoucherRedeemed {}));
+        let msg = QueryMsg::VoucherRedeemed { voucher: voucher("Lazy1") };
+        let res: VoucherRedeemedResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(res.redeemed);
+        let msg = QueryMsg::VoucherRedeemed { voucher: voucher("Lazy2") };
+        let res: VoucherRedeemedResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(!res.redeemed);
+    }
+
+    #[test]
+    fn cw20_lazy_mint_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let key = SigningKey::from_bytes(&[9u8; 32]).unwrap();
+        let creator = key_address(&key);
+        let set_creator = HandleMsg::SetLazyMintCreator {
+            contract_addr: String::from("lazycollection"),
+            creator: Some(creator.to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), set_creator).unwrap();
+        let cw20_voucher = MintVoucher { price: cw20_price(50), ..voucher("Lazy1") };
+        let receive = |amount: u128, voucher: MintVoucher| {
+            let (signature, pubkey) = sign(&key, &voucher);
+            HandleMsg::Receive(Cw20ReceiveMsg {
+                sender: String::from("buyer"),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::RedeemVoucher(RedeemVoucher { voucher, signature, pubkey })).unwrap(),
+            })
+        };
+
+        // each voucher is paid in its own currency only
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(50, "ujuno")), redeem(&key, cw20_voucher.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::Cw20PriceOnly {}));
+        let native = receive(50, voucher("Lazy2"));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), native).unwrap_err();
+        assert!(matches!(err, ContractError::NativePriceOnly {}));
+        let spoofed = receive(50, cw20_voucher.clone());
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), spoofed).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+        let short = receive(40, cw20_voucher.clone());
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), short).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+
+        // the creator is paid in the token, once
+        let res = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive(50, cw20_voucher.clone())).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                cw20_price(0).transfer_msg(&Addr::unchecked(creator), Uint128::new(50)).unwrap(),
+                mint_msg(&Addr::unchecked("lazycollection"), &cw20_voucher, &Addr::unchecked("buyer")).unwrap(),
+            ]
+        );
+        let replay = receive(50, cw20_voucher);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), replay).unwrap_err();
+        assert!(matches!(err, ContractError::VoucherRedeemed {}));
+    }
+
+    #[test]
+    fn cancelled_voucher_cant_be_redeemed() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let key = SigningKey::from_bytes(&[9u8; 32]).unwrap();
+        let creator = key_address(&key);
+        let set_creator = HandleMsg::SetLazyMintCreator {
+            contract_addr: String::from("lazycollection"),
+            creator: Some(creator.to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), set_creator).unwrap();
+
+        // only the creator of the collection cancels its vouchers
+        let cancel = HandleMsg::CancelVoucher { voucher: voucher("Lazy1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info(creator, &[]), cancel.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info(creator, &[]), cancel).unwrap_err();
+        assert!(matches!(err, ContractError::VoucherRedeemed {}));
+
+        // the signed voucher is dead, the other vouchers of the collection are not
+        let funds = coins(50, "ujuno");
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), redeem(&key, voucher("Lazy1"))).unwrap_err();
+        assert!(matches!(err, ContractError::VoucherRedeemed {}));
+        let msg = QueryMsg::VoucherRedeemed { voucher: voucher("Lazy1") };
+        let res: VoucherRedeemedResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(res.redeemed);
+        execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), redeem(&key, voucher("Lazy2"))).unwrap();
+    }
+}
@@ -1,9 +1,21 @@
+pub mod allowlist;
+pub mod auction;
+pub mod bundle;
+pub mod collection_offer;
 pub mod contract;
 mod error;
 pub mod helpers;
+pub mod history;
 pub mod integration_tests;
+pub mod lazy_mint;
+pub mod listing;
 pub mod msg;
+pub mod offer;
+pub mod rental;
+pub mod signed;
 pub mod state;
+pub mod swap;
+pub mod sweep;
 pub mod package;
 
 pub use crate::error::ContractError;
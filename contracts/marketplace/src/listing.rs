@@ -0,0 +1,261 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cw721::{OperatorsResponse, OwnerOfResponse};
+use rvn_base::QueryMsg as Cw721QueryMsg;
+
+use crate::allowlist::check_allowed;
+use crate::contract::{clear_previous_listing, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::ListNft;
+use crate::state::{increment_offerings, offerings, Offering};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * List an nft that stays in the seller wallet. The sender must own it and have approved the
+ * marketplace, which transfers it on the seller's behalf when it is bought
+ */
+pub fn try_list_nft(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ListNft,
+) -> Result<Response, ContractError> {
+    let contract_addr = deps.api.addr_validate(&msg.contract_addr)?;
+    let owner: OwnerOfResponse = deps.querier.query_wasm_smart(
+        &contract_addr,
+        &Cw721QueryMsg::OwnerOf {
+            token_id: msg.token_id.clone(),
+            include_expired: Some(false),
+        },
+    )?;
+    if owner.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !listing_approved(deps.as_ref(), &env, &contract_addr, &msg.token_id, &info.sender)? {
+        return Err(ContractError::NotApproved {});
+    }
+
+    let expires = msg.expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::OfferingExpired {});
+    }
+    let list_price = msg.list_price.validate(deps.api)?;
+    check_allowed(deps.as_ref(), &contract_addr, &list_price)?;
+    clear_previous_listing(deps.branch(), &env, &contract_addr, &msg.token_id)?;
+
+    let id = increment_offerings(deps.storage)?.to_string();
+    let extension = format!("Offer {} from {}", msg.token_id, info.sender);
+    let off = Offering::<String> {
+        owner: info.sender.clone(),
+        contract_addr,
+        token_id: msg.token_id,
+        seller: info.sender.clone(),
+        list_price,
+        price_decline: None,
+        expires,
+        non_custodial: true,
+        reserved_for: None,
+        extension,
+    };
+    offerings::<String>().save(deps.storage, &id, &off)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "list_nft")
+        .add_attribute("offering_id", id)
+        .add_attribute("original_contract", off.contract_addr)
+        .add_attribute("seller", off.seller)
+        .add_attribute("list_price", off.list_price.to_string())
+        .add_attribute("expires", off.expires.to_string())
+        .add_attribute("token_id", off.token_id)
+    )
+}
+
+/**
+ * Remove a non custodial Offering that can't be bought anymore because the seller transferred
+ * the nft or revoked the marketplace approval, anyone can call it
+ */
+pub fn try_prune_listing(
+    deps: DepsMut,
+    env: Env,
+    offering_id: String,
+) -> Result<Response, ContractError> {
+    let off = offerings::<String>().load(deps.storage, &offering_id)?;
+    if !off.non_custodial || listing_approved(deps.as_ref(), &env, &off.contract_addr, &off.token_id, &off.seller)? {
+        return Err(ContractError::ListingNotStale {});
+    }
+    offerings::<String>().remove(deps.storage, &offering_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "prune_listing")
+        .add_attribute("offering_id", offering_id)
+        .add_attribute("seller", off.seller)
+    )
+}
+
+/**
+ * Whether `seller` still owns the nft and the marketplace may transfer it, through an approval
+ * on the token or an operator approval on all the seller's tokens
+ */
+pub(crate) fn listing_approved(
+    deps: Deps,
+    env: &Env,
+    contract_addr: &Addr,
+    token_id: &str,
+    seller: &Addr,
+) -> StdResult<bool> {
+    let owner_query = Cw721QueryMsg::OwnerOf {
+        token_id: token_id.to_string(),
+        include_expired: Some(false),
+    };
+    // a burnt token has no owner anymore
+    let owner: OwnerOfResponse = match deps.querier.query_wasm_smart(contract_addr, &owner_query) {
+        Ok(owner) => owner,
+        Err(_) => return Ok(false),
+    };
+    if owner.owner != *seller {
+        return Ok(false);
+    }
+    let marketplace = env.contract.address.as_str();
+    if owner.approvals.iter().any(|a| a.spender == marketplace) {
+        return Ok(true);
+    }
+
+    // page through the operators, rvn-base caps its pages at the same MAX_LIMIT
+    let mut start_after = None;
+    loop {
+        let page: OperatorsResponse = deps.querier.query_wasm_smart(
+            contract_addr,
+            &Cw721QueryMsg::AllOperators {
+                owner: seller.to_string(),
+                include_expired: Some(false),
+                start_after,
+                limit: Some(MAX_LIMIT),
+            },
+        )?;
+        if page.operators.iter().any(|a| a.spender == marketplace) {
+            return Ok(true);
+        }
+        match page.operators.last() {
+            Some(last) if page.operators.len() == MAX_LIMIT as usize => start_after = Some(last.spender.clone()),
+            _ => return Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::contract::tests::init_market;
+    use crate::contract::{execute, transfer_nft_msg};
+    use crate::msg::HandleMsg;
+    use crate::package::Price;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{
+        coin, coins, from_binary, to_binary, ContractResult, OwnedDeps, SystemError, SystemResult, WasmQuery,
+    };
+    use cw721::Approval;
+    use cw_utils::Expiration;
+
+    // "nftcontract" reports `owner` as the owner of every token, with the marketplace approved
+    // on the token or as an operator
+    pub(crate) fn mock_nft(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        owner: &'static str,
+        token_approved: bool,
+        operator_approved: bool,
+    ) {
+        let marketplace = Approval {
+            spender: mock_env().contract.address.to_string(),
+            expires: Expiration::Never {},
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "nftcontract" => {
+                let res = match from_binary(msg).unwrap() {
+                    Cw721QueryMsg::OwnerOf { .. } => to_binary(&OwnerOfResponse {
+                        owner: owner.to_string(),
+                        approvals: if token_approved { vec![marketplace.clone()] } else { vec![] },
+                    }),
+                    Cw721QueryMsg::AllOperators { .. } => to_binary(&OperatorsResponse {
+                        operators: if operator_approved { vec![marketplace.clone()] } else { vec![] },
+                    }),
+                    // no royalty configured
+                    _ => return SystemResult::Ok(ContractResult::Err("not supported".to_string())),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract { addr: "unknown".to_string() }),
+        });
+    }
+
+    fn list_msg() -> HandleMsg {
+        HandleMsg::ListNft(ListNft {
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from("WalletNFT"),
+            list_price: Price::Native(coin(100, "ujuno")),
+            expires: None,
+        })
+    }
+
+    #[test]
+    fn non_custodial_listing_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        // only the owner can list, once the marketplace is approved
+        mock_nft(&mut deps, "seller", false, false);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), list_msg()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list_msg()).unwrap_err();
+        assert!(matches!(err, ContractError::NotApproved {}));
+
+        mock_nft(&mut deps, "seller", false, true);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list_msg()).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(offerings::<String>().load(&deps.storage, "1").unwrap().non_custodial);
+
+        // a valid listing can't be pruned, and is bought straight from the seller wallet
+        let prune = HandleMsg::PruneListing { offering_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), prune).unwrap_err();
+        assert!(matches!(err, ContractError::ListingNotStale {}));
+        let buy = HandleMsg::BuyNft { offering_id: String::from("1") };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "WalletNFT").unwrap()
+        );
+    }
+
+    #[test]
+    fn stale_listing_is_pruned() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_nft(&mut deps, "seller", true, false);
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list_msg()).unwrap();
+
+        // the seller sold the nft elsewhere
+        mock_nft(&mut deps, "someoneelse", true, false);
+        let buy = HandleMsg::BuyNft { offering_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap_err();
+        assert!(matches!(err, ContractError::StaleListing {}));
+
+        let prune = HandleMsg::PruneListing { offering_id: String::from("1") };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), prune).unwrap();
+        assert!(offerings::<String>().may_load(&deps.storage, "1").unwrap().is_none());
+    }
+
+    #[test]
+    fn stale_listing_is_replaced_on_relist() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_nft(&mut deps, "seller", true, false);
+        execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list_msg()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), list_msg()).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyListed {}));
+
+        // the new owner can list the nft, the previous seller's listing is dropped
+        mock_nft(&mut deps, "newowner", true, false);
+        execute(deps.as_mut(), mock_env(), mock_info("newowner", &[]), list_msg()).unwrap();
+        assert!(offerings::<String>().may_load(&deps.storage, "1").unwrap().is_none());
+        assert_eq!(offerings::<String>().load(&deps.storage, "2").unwrap().seller, "newowner");
+    }
+}
@@ -1,8 +1,13 @@
+use cosmwasm_std::{Binary, Timestamp, Uint128};
 use cw721::Cw721ReceiveMsg;
-use cw20::{Cw20CoinVerified, Cw20ReceiveMsg};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::Expiration;
+use rvn_base::Extension;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::package::Price;
+
 /**
  * @owner KevinNguyen <kevinnguyen.asian.vie@gmail.com>
  * @date Create in 2022-29-06 12:59:00
@@ -12,9 +17,26 @@ use serde::{Deserialize, Serialize};
 pub struct InitMsg {
     pub count: i32,
     pub name: String,
+    // admin allowed to update the config, defaults to the instantiating address
+    pub admin: Option<String>,
+    // protocol fee taken from every sale, in basis points (1/10000), at most 1000
+    pub fee_bps: u64,
+    // address receiving the protocol fee, defaults to the admin
+    pub fee_recipient: Option<String>,
+    // accept any collection and currency, defaults to true
+    pub open_mode: Option<bool>,
 }
 
 
+// MigrateMsg creates the config of a marketplace deployed before it existed, it is ignored
+// when the config is already stored
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    pub admin: Option<String>,
+    pub fee_bps: Option<u64>,
+    pub fee_recipient: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub count: i32,
@@ -27,15 +49,160 @@ pub enum HandleMsg {
     Increment {},
     Reset {},
     WithdrawNft { offering_id: String },
+    // ListNft lists an nft kept in the seller wallet, the marketplace must be approved on it
+    ListNft(ListNft),
+    // PruneListing removes a ListNft offering whose owner changed or approval was revoked
+    PruneListing { offering_id: String },
+    // UpdatePrice changes the price of an Offering in place, seller only
+    UpdatePrice { offering_id: String, list_price: Price },
+    // ReclaimExpired returns the nft of an expired Offering to its seller, anyone can call it
+    ReclaimExpired { offering_id: String },
+    // BuyNft pays for an Offering listed in a native coin with the attached funds
+    BuyNft { offering_id: String },
+    // BuyMany buys several Offerings listed in the same native coin with the attached funds,
+    // failing if one is gone or their total price exceeds `max_total`
+    BuyMany(BuyMany),
+    // UpdateConfig changes the protocol fee settings and open mode, admin only
+    UpdateConfig {
+        fee_bps: Option<u64>,
+        fee_recipient: Option<String>,
+        open_mode: Option<bool>,
+    },
+    // CreateBundle starts a draft bundle, nfts are then sent with `AddToBundle` or approved and
+    // added with `AddApprovedToBundle`
+    CreateBundle {},
+    // AddApprovedToBundle adds an nft kept in the seller wallet to a draft bundle, the
+    // marketplace must be approved on it
+    AddApprovedToBundle {
+        bundle_id: String,
+        contract_addr: String,
+        token_id: String,
+    },
+    // PublishBundle puts a draft bundle up for sale at `list_price`, seller only
+    PublishBundle { bundle_id: String, list_price: Price },
+    // BuyBundle pays for a bundle listed in a native coin with the attached funds
+    BuyBundle { bundle_id: String },
+    // WithdrawBundle removes a bundle and returns every nft to the seller, seller only
+    WithdrawBundle { bundle_id: String },
+    // ProposeAdmin starts the transfer of the admin role to `admin`, admin only
+    ProposeAdmin { admin: String },
+    // AcceptAdmin completes the transfer, called by the proposed admin
+    AcceptAdmin {},
+    // Pause stops listings and sales, withdrawals and refunds keep working, admin only
+    Pause {},
+    // Unpause resumes listings and sales, admin only
+    Unpause {},
+    // UpdateAllowList adds and removes nft contracts and currencies (native denoms or CW20
+    // addresses) that can be listed outside open mode, admin only
+    UpdateAllowList {
+        add_collections: Vec<String>,
+        remove_collections: Vec<String>,
+        add_currencies: Vec<String>,
+        remove_currencies: Vec<String>,
+    },
+    // PlaceBid bids on an Auction in a native coin with the attached funds
+    PlaceBid { auction_id: String },
+    // SettleAuction closes an Auction once its end time has passed, anyone can call it
+    SettleAuction { auction_id: String },
+    // PlaceOffer escrows the attached native funds as an offer on any nft
+    PlaceOffer(PlaceOffer),
+    // CancelOffer refunds an offer, by its bidder or by anyone once it has expired
+    CancelOffer { offer_id: String },
+    // PlaceCollectionOffer escrows the attached native funds as a bid on any nft of a collection
+    PlaceCollectionOffer(PlaceCollectionOffer),
+    // CancelCollectionOffer refunds the unfilled escrow, by its bidder or by anyone once expired
+    CancelCollectionOffer { offer_id: String },
+    // ListRental offers an nft kept in the owner wallet for rent, the marketplace must be
+    // approved on it to set its user
+    ListRental(ListRental),
+    // CancelRental removes a rental listing, the current renter keeps the nft until its rental
+    // expires, owner only
+    CancelRental { rental_id: String },
+    // Rent pays a rental listed in a native coin with the attached funds
+    Rent(Rent),
+    // CreateSwap proposes to trade nfts with `counterparty`, an optional native sweetener is
+    // escrowed from the attached funds. Both sides then send their nfts with `DepositToSwap`
+    CreateSwap(CreateSwap),
+    // CancelSwap returns every deposit of a swap to its owner, by either party
+    CancelSwap { swap_id: String },
+    // BuySigned pays a SignedOrder listed in a native coin with the attached funds
+    BuySigned(BuySigned),
+    // CancelSignedOrders spends `nonces` of the sender, its signed orders using them can't be bought
+    CancelSignedOrders { nonces: Vec<u64> },
+    // SetLazyMintCreator lets `creator` sign the MintVoucher of `contract_addr`, an rvn-base
+    // collection whose minter is the marketplace. No creator stops its lazy minting, admin only
+    SetLazyMintCreator { contract_addr: String, creator: Option<String> },
+    // RedeemVoucher mints the nft of a MintVoucher priced in a native coin to the sender, paid with
+    // the attached funds
+    RedeemVoucher(RedeemVoucher),
+    // CancelVoucher marks a MintVoucher as redeemed without minting it, by the lazy mint creator of
+    // its collection
+    CancelVoucher { voucher: MintVoucher },
     Receive(Cw20ReceiveMsg),
     ReceiveNft(Cw721ReceiveMsg),
 }
 
+// ReceiveNftMsg is the payload of an nft sent to the marketplace with `SendNft`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveNftMsg {
+    SellNft(SellNft),
+    SellDutch(SellDutch),
+    StartAuction(StartAuction),
+    AcceptOffer(AcceptOffer),
+    AcceptCollectionOffer(AcceptOffer),
+    AddToBundle(AddToBundle),
+    DepositToSwap(DepositToSwap),
+}
+
+// ReceiveMsg is the payload of CW20 tokens sent to the marketplace with `Send`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    BuyNft(BuyNft),
+    PlaceBid(PlaceBid),
+    PlaceOffer(PlaceOffer),
+    PlaceCollectionOffer(PlaceCollectionOffer),
+    BuyBundle(BuyBundle),
+    BuyMany(BuyMany),
+    CreateSwap(CreateSwap),
+    Rent(Rent),
+    BuySigned(BuySigned),
+    RedeemVoucher(RedeemVoucher),
+}
+
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct SellNft {
-    pub list_price: Cw20CoinVerified,
+    pub list_price: Price,
+    // the offering can't be bought anymore after `expires`, never if unset
+    pub expires: Option<Expiration>,
+    // only this address can buy a private offering, it is left out of the public listings
+    pub reserved_for: Option<String>,
+}
+
+// ListNft lists `token_id` of `contract_addr` without sending it, the sender must own it and
+// have granted the marketplace an `Approve` or `ApproveAll`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ListNft {
+    pub contract_addr: String,
+    pub token_id: String,
+    pub list_price: Price,
+    pub expires: Option<Expiration>,
+}
+
+// SellDutch lists the nft at a price falling from `list_price` to `floor_price` between
+// `start_time` (now if unset) and `end_time`, every `step_interval` seconds or continuously
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SellDutch {
+    pub list_price: Price,
+    pub floor_price: Uint128,
+    pub start_time: Option<Timestamp>,
+    pub end_time: Timestamp,
+    pub step_interval: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -44,6 +211,169 @@ pub struct BuyNft {
     pub offering_id: String, 
 }
 
+// StartAuction opens an English auction, bids are placed in the currency of `reserve_price`
+// and the nft is only sold if the highest bid reaches it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct StartAuction {
+    pub reserve_price: Price,
+    pub end_time: Timestamp,
+    // a new bid must exceed the highest one by at least this amount
+    pub min_increment: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PlaceBid {
+    pub auction_id: String,
+}
+
+
+// PlaceOffer bids on `token_id` of `contract_addr` until `expires` (never if unset)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PlaceOffer {
+    pub contract_addr: String,
+    pub token_id: String,
+    pub expires: Option<Expiration>,
+}
+
+// PlaceCollectionOffer bids on `quantity` nfts of `contract_addr`, the escrow is split evenly
+// between them so it must be a multiple of `quantity`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PlaceCollectionOffer {
+    pub contract_addr: String,
+    pub quantity: u32,
+    pub expires: Option<Expiration>,
+}
+
+// AddToBundle is sent by the seller along with the nft to add it to a draft bundle
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AddToBundle {
+    pub bundle_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BuyBundle {
+    pub bundle_id: String,
+}
+
+// BuyMany sweeps `offering_ids` at their current prices, paid at once in their common currency
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BuyMany {
+    pub offering_ids: Vec<String>,
+    pub max_total: Uint128,
+}
+
+// ListRental rents `token_id` of `contract_addr` out at `price_per_period` for every `period`
+// seconds, up to `max_periods` at once
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ListRental {
+    pub contract_addr: String,
+    pub token_id: String,
+    pub price_per_period: Price,
+    pub period: u64,
+    pub max_periods: u32,
+}
+
+// Rent makes the sender the user of a rental nft for `periods` periods
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Rent {
+    pub rental_id: String,
+    pub periods: u32,
+}
+
+// SignedOrder lists `token_id` of `contract_addr` at `price` without a transaction from the
+// seller: the seller signs it off-chain with the secp256k1 key of its account and approves the
+// marketplace on the nft contract. The signed bytes are the sha256 of the marketplace address
+// followed by the json of the order, see `signed::signed_digest`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SignedOrder {
+    pub seller: String,
+    pub contract_addr: String,
+    pub token_id: String,
+    pub price: Price,
+    pub expires: Expiration,
+    // a nonce is spent by the first purchase or cancellation of an order using it
+    pub nonce: u64,
+}
+
+// BuySigned buys a SignedOrder, `pubkey` is the compressed secp256k1 public key of the seller
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BuySigned {
+    pub order: SignedOrder,
+    pub signature: Binary,
+    pub pubkey: Binary,
+}
+
+// MintVoucher sells an nft of `contract_addr` that is minted at purchase: the lazy mint creator
+// of the collection signs it off-chain like a SignedOrder and is paid `price` when it is redeemed.
+// Each voucher can be redeemed once
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MintVoucher {
+    pub contract_addr: String,
+    pub token_id: String,
+    pub token_uri: Option<String>,
+    pub extension: Extension,
+    pub price: Price,
+    pub expires: Expiration,
+}
+
+// RedeemVoucher buys a MintVoucher, `pubkey` is the compressed secp256k1 public key of the creator
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RedeemVoucher {
+    pub voucher: MintVoucher,
+    pub signature: Binary,
+    pub pubkey: Binary,
+}
+
+// CreateSwap trades the `offered` nfts of the sender, and its optional sweetener, for the
+// `wanted` nfts of `counterparty`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CreateSwap {
+    pub counterparty: String,
+    pub offered: Vec<SwapToken>,
+    pub wanted: Vec<SwapToken>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SwapToken {
+    pub contract_addr: String,
+    pub token_id: String,
+}
+
+// DepositToSwap is sent by either party along with one of its nfts of the swap
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DepositToSwap {
+    pub swap_id: String,
+}
+
+// AcceptOffer is sent by the nft owner along with the nft to sell it to the bidder
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AcceptOffer {
+    pub offer_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -58,8 +388,145 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     // GetCount returns the current count as a json-encoded number
     GetCount {},
-    // GetOffering returns a list of all offerings
-    GetOfferings {},
+    // GetOffering returns a list of all public offerings, unset or false will filter out expired ones
+    GetOfferings {
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Offering returns a single offering
+    Offering { offering_id: String },
+    // ReservedFor returns the unexpired private offerings only `buyer` can buy, paginated by offering id
+    ReservedFor {
+        buyer: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // OfferingsBySeller returns the public offerings of a seller, paginated by offering id
+    OfferingsBySeller {
+        seller: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // OfferingsByContract returns the public offerings of an nft contract, paginated by offering id
+    OfferingsByContract {
+        contract_addr: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // OfferingByToken returns the offering of an nft, if it is listed publicly
+    OfferingByToken { contract_addr: String, token_id: String },
+    // OfferingsByPrice returns the public fixed price offerings of a collection in `currency` (a native denom or a
+    // CW20 address) sorted by list price, between `min` and `max` included. `start_after` is the
+    // (list price, offering id) `next_start_after` of the previous page
+    OfferingsByPrice {
+        contract_addr: String,
+        currency: String,
+        min: Option<Uint128>,
+        max: Option<Uint128>,
+        order: Option<SortOrder>,
+        start_after: Option<(Uint128, String)>,
+        limit: Option<u32>,
+    },
+    // FloorPrice returns the cheapest unexpired public offering of a collection in `currency`, dutch
+    // listings compared at their current price
+    FloorPrice { contract_addr: String, currency: String },
+    // OfferingsByOwner returns the public offerings of an owner, paginated by offering id
+    OfferingsByOwner {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Config returns the admin and protocol fee settings
+    Config {},
+    // Bundle returns a single bundle, draft or for sale
+    Bundle { bundle_id: String },
+    // Bundles returns the bundles, paginated by bundle id
+    Bundles {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Rental returns a single rental listing
+    Rental { rental_id: String },
+    // Rentals returns the rental listings, paginated by rental id
+    Rentals {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Swap returns a single swap
+    Swap { swap_id: String },
+    // Swaps returns the open swaps, paginated by swap id
+    Swaps {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // NonceUsed returns whether `nonce` of `seller` was spent by a sale or a cancellation
+    NonceUsed { seller: String, nonce: u64 },
+    // LazyMintCreator returns the creator signing the vouchers of a collection, if any
+    LazyMintCreator { contract_addr: String },
+    // VoucherRedeemed returns whether a MintVoucher was already redeemed
+    VoucherRedeemed { voucher: MintVoucher },
+    // Trades returns the sale history of the marketplace, paginated by trade id
+    Trades {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // TradesByCollection returns the sale history of an nft contract, paginated by trade id
+    TradesByCollection {
+        contract_addr: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // CollectionStats returns the trading stats of an nft contract, one entry per currency
+    // paginated by currency
+    CollectionStats {
+        contract_addr: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // AllCollectionStats returns the trading stats of every traded nft contract, paginated by
+    // (contract, currency)
+    AllCollectionStats {
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    },
+    // AllowedCollections returns the allow-listed nft contracts, paginated by address
+    AllowedCollections {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // AllowedCurrencies returns the allow-listed currencies, paginated by denom or address
+    AllowedCurrencies {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Auction returns a single auction
+    Auction { auction_id: String },
+    // Auctions returns the running auctions, paginated by auction id
+    Auctions {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Offer returns a single offer
+    Offer { offer_id: String },
+    // OffersByToken returns the offers on an nft, paginated by offer id
+    OffersByToken {
+        contract_addr: String,
+        token_id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // CollectionOffer returns a single collection offer
+    CollectionOffer { offer_id: String },
+    // BestCollectionOffer returns the highest unexpired collection offer in `currency`,
+    // a native denom or a CW20 address
+    BestCollectionOffer { contract_addr: String, currency: String },
+    // OffersByBidder returns the offers of a bidder, paginated by offer id
+    OffersByBidder {
+        bidder: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 
@@ -0,0 +1,330 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult};
+use cw20::{Cw20CoinVerified, Cw20ReceiveMsg};
+use cw721::Cw721ReceiveMsg;
+use cw_storage_plus::Bound;
+use cw_utils::one_coin;
+
+use crate::allowlist::check_allowed;
+use crate::contract::{check_cw20_sender, sale_payout, transfer_nft_msg, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::msg::{AcceptOffer, PlaceOffer};
+use crate::package::{OffersResponse, Price, QueryOfferResult};
+use crate::state::{increment_offers, offers, Offer};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Place an offer on an nft, escrowing the single native coin attached to the message
+ */
+pub fn try_place_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: PlaceOffer,
+) -> Result<Response, ContractError> {
+    let price = Price::Native(one_coin(&info)?);
+    save_offer(deps, env, info.sender, price, msg)
+}
+
+/**
+ * Place an offer on an nft, escrowing the CW20 tokens sent through the `Send` hook
+ */
+pub fn try_receive_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: PlaceOffer,
+) -> Result<Response, ContractError> {
+    check_cw20_sender(deps.as_ref(), &info.sender)?;
+    let bidder = deps.api.addr_validate(&rcv_msg.sender)?;
+    let price = Price::Cw20(Cw20CoinVerified {
+        address: info.sender,
+        amount: rcv_msg.amount,
+    });
+    save_offer(deps, env, bidder, price, msg)
+}
+
+fn save_offer(
+    deps: DepsMut,
+    env: Env,
+    bidder: Addr,
+    price: Price,
+    msg: PlaceOffer,
+) -> Result<Response, ContractError> {
+    if price.amount().is_zero() {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    let expires = msg.expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::OfferExpired {});
+    }
+    let contract_addr = deps.api.addr_validate(&msg.contract_addr)?;
+    check_allowed(deps.as_ref(), &contract_addr, &price)?;
+
+    let id = increment_offers(deps.storage)?.to_string();
+    let offer = Offer {
+        bidder,
+        contract_addr,
+        token_id: msg.token_id,
+        price,
+        expires,
+    };
+    offers().save(deps.storage, &id, &offer)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "place_offer")
+        .add_attribute("offer_id", id)
+        .add_attribute("bidder", offer.bidder)
+        .add_attribute("contract_addr", offer.contract_addr)
+        .add_attribute("token_id", offer.token_id)
+        .add_attribute("price", offer.price.to_string())
+        .add_attribute("expires", offer.expires.to_string())
+    )
+}
+
+/**
+ * Cancel an offer and refund its escrow to the bidder. The bidder can cancel at any time,
+ * anyone else only once the offer has expired
+ */
+pub fn try_cancel_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer_id: String,
+) -> Result<Response, ContractError> {
+    let offer = offers().load(deps.storage, &offer_id)?;
+    if offer.bidder != info.sender && !offer.expires.is_expired(&env.block) {
+        return Err(ContractError::Unauthorized {});
+    }
+    offers().remove(deps.storage, &offer_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_offer")
+        .add_attribute("offer_id", offer_id)
+        .add_attribute("bidder", offer.bidder.clone())
+        .add_message(offer.price.transfer_msg(&offer.bidder, offer.price.amount())?)
+    )
+}
+
+/**
+ * Accept an offer with the nft sent with `SendNft`, `info.sender` is the nft contract and
+ * `rcv_msg.sender` the owner being paid
+ */
+pub fn try_accept_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: AcceptOffer,
+) -> Result<Response, ContractError> {
+    let offer = offers().load(deps.storage, &msg.offer_id)?;
+    if offer.contract_addr != info.sender || offer.token_id != rcv_msg.token_id {
+        return Err(ContractError::OfferTokenMismatch {});
+    }
+    if offer.expires.is_expired(&env.block) {
+        return Err(ContractError::OfferExpired {});
+    }
+    // the collection or the currency may have left the allow-lists since the offer was placed
+    check_allowed(deps.as_ref(), &offer.contract_addr, &offer.price)?;
+    offers().remove(deps.storage, &msg.offer_id)?;
+
+    let seller = deps.api.addr_validate(&rcv_msg.sender)?;
+    let amount = offer.price.amount();
+    let payout = sale_payout(
+        deps.as_ref(),
+        &offer.price,
+        amount,
+        &offer.contract_addr,
+        &offer.token_id,
+        &seller,
+    )?;
+    record_sale(deps.storage, &env, &offer.contract_addr, &offer.token_id, &seller, &offer.bidder, offer.price.clone())?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_offer")
+        .add_attribute("offer_id", msg.offer_id)
+        .add_attribute("buyer", offer.bidder.clone())
+        .add_attribute("seller", seller)
+        .add_attribute("paid_price", amount)
+        .add_attribute("fee", payout.fee)
+        .add_attribute("royalty", payout.royalty)
+        .add_attribute("token_id", offer.token_id.clone())
+        .add_attribute("contract_addr", offer.contract_addr.clone())
+        .add_messages(payout.messages)
+        .add_message(transfer_nft_msg(&offer.contract_addr, &offer.bidder, &offer.token_id)?)
+    )
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_offer(deps: Deps, offer_id: String) -> StdResult<QueryOfferResult> {
+    let offer = offers().load(deps.storage, &offer_id)?;
+    Ok(parse_offer(offer_id, offer))
+}
+
+pub fn query_offers_by_token(
+    deps: Deps,
+    contract_addr: String,
+    token_id: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let contract_addr = deps.api.addr_validate(&contract_addr)?;
+
+    let offers = offers()
+        .idx
+        .token
+        .prefix((contract_addr, token_id))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, offer)| parse_offer(id, offer)))
+        .collect::<StdResult<_>>()?;
+    Ok(OffersResponse { offers })
+}
+
+pub fn query_offers_by_bidder(
+    deps: Deps,
+    bidder: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let bidder = deps.api.addr_validate(&bidder)?;
+
+    let offers = offers()
+        .idx
+        .bidder
+        .prefix(bidder)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, offer)| parse_offer(id, offer)))
+        .collect::<StdResult<_>>()?;
+    Ok(OffersResponse { offers })
+}
+
+fn parse_offer(id: String, offer: Offer) -> QueryOfferResult {
+    QueryOfferResult {
+        id,
+        bidder: offer.bidder,
+        contract_addr: offer.contract_addr,
+        token_id: offer.token_id,
+        price: offer.price,
+        expires: offer.expires,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market, mock_cw20};
+    use crate::contract::{execute, query};
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg, ReceiveNftMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, from_binary, to_binary, BankMsg, CosmosMsg, Uint128};
+    use cw_utils::Expiration;
+
+    fn place_offer_msg(token_id: &str, expires: Option<Expiration>) -> PlaceOffer {
+        PlaceOffer {
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from(token_id),
+            expires,
+        }
+    }
+
+    fn accept_offer_msg(sender: &str, token_id: &str, offer_id: &str) -> HandleMsg {
+        HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: String::from(sender),
+            token_id: String::from(token_id),
+            msg: to_binary(&ReceiveNftMsg::AcceptOffer(AcceptOffer { offer_id: String::from(offer_id) })).unwrap(),
+        })
+    }
+
+    #[test]
+    fn native_offer_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        let expires = Some(Expiration::AtHeight(mock_env().block.height + 100));
+        let msg = HandleMsg::PlaceOffer(place_offer_msg("Unlisted", expires));
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(100, "ujuno")), msg).unwrap();
+        let msg = HandleMsg::PlaceOffer(place_offer_msg("Other", None));
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(30, "ujuno")), msg).unwrap();
+
+        // offers are listed by token and by bidder
+        let by_token = QueryMsg::OffersByToken {
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from("Unlisted"),
+            start_after: None,
+            limit: None,
+        };
+        let res: OffersResponse = from_binary(&query(deps.as_ref(), mock_env(), by_token).unwrap()).unwrap();
+        assert_eq!(res.offers.len(), 1);
+        assert_eq!(res.offers[0].id, "1");
+        let by_bidder = QueryMsg::OffersByBidder { bidder: String::from("alice"), start_after: Some(String::from("1")), limit: None };
+        let res: OffersResponse = from_binary(&query(deps.as_ref(), mock_env(), by_bidder).unwrap()).unwrap();
+        assert_eq!(res.offers.len(), 1);
+        assert_eq!(res.offers[0].token_id, "Other");
+
+        // the nft must match the offer
+        let err = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept_offer_msg("owner", "Other", "1")).unwrap_err();
+        assert!(matches!(err, ContractError::OfferTokenMismatch {}));
+
+        // the owner is paid and the nft goes to the bidder
+        let res = execute(deps.as_mut(), mock_env(), mock_info("nftcontract", &[]), accept_offer_msg("owner", "Unlisted", "1")).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "owner".to_string(), amount: coins(100, "ujuno") })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("alice"), "Unlisted").unwrap()
+        );
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::Offer { offer_id: String::from("1") }).is_err());
+
+        // only the bidder can cancel before expiry, and is refunded
+        let cancel = HandleMsg::CancelOffer { offer_id: String::from("2") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), cancel).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "alice".to_string(), amount: coins(30, "ujuno") })
+        );
+    }
+
+    #[test]
+    fn cw20_offer_expires() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_cw20(&mut deps);
+
+        let expires = Some(Expiration::AtTime(mock_env().block.time.plus_seconds(60)));
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("alice"),
+            amount: Uint128::new(80),
+            msg: to_binary(&ReceiveMsg::PlaceOffer(place_offer_msg("Unlisted", expires))).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Offer { offer_id: String::from("1") }).unwrap();
+        let offer: QueryOfferResult = from_binary(&res).unwrap();
+        assert_eq!(offer.price, cw20_price(80));
+
+        // an expired offer can't be accepted, anyone can then refund it
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(60);
+        let err = execute(deps.as_mut(), later.clone(), mock_info("nftcontract", &[]), accept_offer_msg("owner", "Unlisted", "1")).unwrap_err();
+        assert!(matches!(err, ContractError::OfferExpired {}));
+        let cancel = HandleMsg::CancelOffer { offer_id: String::from("1") };
+        let res = execute(deps.as_mut(), later, mock_info("anyone", &[]), cancel).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            cw20_price(0).transfer_msg(&Addr::unchecked("alice"), Uint128::new(80)).unwrap()
+        );
+    }
+}
@@ -1,12 +1,17 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 #![allow(unused_imports)]
-use cosmwasm_std::{Addr, Response};
-use cw20::Cw20CoinVerified;
+use cosmwasm_std::{
+    to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Response, StdResult, Timestamp, Uint128, WasmMsg,
+};
+use cw20::{Cw20CoinVerified, Cw20ExecuteMsg};
+use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
+use std::fmt;
 
 use crate::error::{ContractError};
+use crate::state::{Bid, BundleItem, PriceDecline, SwapItem};
 
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -15,11 +20,95 @@ pub struct ContractInfoResponse {
 }
 
 
+/**
+ * Price of an Offering, either a native bank coin or an amount of a CW20 token
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Price {
+    Native(Coin),
+    Cw20(Cw20CoinVerified),
+}
+
+impl Price {
+    pub fn amount(&self) -> Uint128 {
+        match self {
+            Price::Native(coin) => coin.amount,
+            Price::Cw20(coin) => coin.amount,
+        }
+    }
+
+    // same currency for another amount
+    pub fn with_amount(&self, amount: Uint128) -> Price {
+        match self {
+            Price::Native(coin) => Price::Native(Coin { denom: coin.denom.clone(), amount }),
+            Price::Cw20(coin) => Price::Cw20(Cw20CoinVerified { address: coin.address.clone(), amount }),
+        }
+    }
+
+    // native denom or CW20 address, prices are only comparable within the same currency
+    pub fn currency(&self) -> String {
+        match self {
+            Price::Native(coin) => coin.denom.clone(),
+            Price::Cw20(coin) => coin.address.to_string(),
+        }
+    }
+
+    // validate the CW20 address when the price comes from an untrusted message
+    pub fn validate(self, api: &dyn Api) -> StdResult<Price> {
+        match self {
+            Price::Native(coin) => Ok(Price::Native(coin)),
+            Price::Cw20(coin) => Ok(Price::Cw20(Cw20CoinVerified {
+                address: api.addr_validate(coin.address.as_str())?,
+                amount: coin.amount,
+            })),
+        }
+    }
+
+    // build the message paying `amount` of this currency from the marketplace to `recipient`
+    pub fn transfer_msg(&self, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+        match self {
+            Price::Native(coin) => Ok(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin {
+                    denom: coin.denom.clone(),
+                    amount,
+                }],
+            }
+            .into()),
+            Price::Cw20(coin) => Ok(WasmMsg::Execute {
+                contract_addr: coin.address.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }
+            .into()),
+        }
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Price::Native(coin) => write!(f, "{} {}", coin.amount, coin.denom),
+            Price::Cw20(coin) => write!(f, "{} {}", coin.amount, coin.address),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct QueryOfferingResult {
     pub id: String,
     pub token_id: String,
-    pub list_price: Cw20CoinVerified,
+    pub list_price: Price,
+    // price to pay right now, differs from `list_price` for dutch listings
+    pub current_price: Price,
+    pub price_decline: Option<PriceDecline>,
+    pub expires: Expiration,
+    pub non_custodial: bool,
+    pub reserved_for: Option<Addr>,
     pub contract_addr: Addr,
     pub seller: Addr,
     pub owner: Addr,
@@ -29,4 +118,179 @@ pub struct QueryOfferingResult {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct OfferingResponse {
     pub offerings: Vec<QueryOfferingResult>,
-}
\ No newline at end of file
+    // `start_after` of the next page, None once every offering was read. A page skipping hidden
+    // offerings can hold fewer offerings than asked, even none, and still have a next page
+    pub next_start_after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OfferingsByPriceResponse {
+    pub offerings: Vec<QueryOfferingResult>,
+    // `start_after` of the next page, None once every offering in the price range was read
+    pub next_start_after: Option<(Uint128, String)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryAuctionResult {
+    pub id: String,
+    pub token_id: String,
+    pub contract_addr: Addr,
+    pub seller: Addr,
+    pub reserve_price: Price,
+    pub end_time: Timestamp,
+    pub min_increment: Uint128,
+    pub highest_bid: Option<Bid>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuctionsResponse {
+    pub auctions: Vec<QueryAuctionResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryOfferResult {
+    pub id: String,
+    pub bidder: Addr,
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub price: Price,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OffersResponse {
+    pub offers: Vec<QueryOfferResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryCollectionOfferResult {
+    pub id: String,
+    pub bidder: Addr,
+    pub contract_addr: Addr,
+    // price paid for each token
+    pub price: Price,
+    // tokens still wanted
+    pub quantity: u32,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BestCollectionOfferResponse {
+    pub offer: Option<QueryCollectionOfferResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FloorPriceResponse {
+    pub offering: Option<QueryOfferingResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OfferingByTokenResponse {
+    pub offering: Option<QueryOfferingResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowedCollectionsResponse {
+    pub collections: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowedCurrenciesResponse {
+    pub currencies: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NonceUsedResponse {
+    // the signed orders with this nonce were bought or cancelled
+    pub used: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LazyMintCreatorResponse {
+    pub creator: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoucherRedeemedResponse {
+    pub redeemed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryBundleResult {
+    pub id: String,
+    pub seller: Addr,
+    pub items: Vec<BundleItem>,
+    // None while the bundle is a draft
+    pub list_price: Option<Price>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BundlesResponse {
+    pub bundles: Vec<QueryBundleResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryTradeResult {
+    pub id: u64,
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub seller: Addr,
+    pub buyer: Addr,
+    pub price: Price,
+    pub time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TradesResponse {
+    pub trades: Vec<QueryTradeResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryCollectionStatsResult {
+    pub contract_addr: Addr,
+    // native denom or CW20 address the stats are counted in
+    pub currency: String,
+    pub volume: Uint128,
+    pub sales: u64,
+    pub last_sale: Uint128,
+    pub highest_sale: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CollectionStatsResponse {
+    pub stats: Vec<QueryCollectionStatsResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QuerySwapResult {
+    pub id: String,
+    pub maker: Addr,
+    pub counterparty: Addr,
+    pub offered: Vec<SwapItem>,
+    pub wanted: Vec<SwapItem>,
+    pub sweetener: Option<Price>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapsResponse {
+    pub swaps: Vec<QuerySwapResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueryRentalResult {
+    pub id: String,
+    pub owner: Addr,
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub price_per_period: Price,
+    pub period: u64,
+    pub max_periods: u32,
+    // only set while the nft is rented
+    pub renter: Option<Addr>,
+    pub rented_until: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RentalsResponse {
+    pub rentals: Vec<QueryRentalResult>,
+}
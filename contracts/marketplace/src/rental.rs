@@ -0,0 +1,345 @@
+use cosmwasm_std::{to_binary, Addr, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Timestamp, Uint128, WasmMsg};
+use cw20::Cw20ReceiveMsg;
+use cw721::OwnerOfResponse;
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, Expiration};
+use rvn_base::{ExecuteMsg as Cw721ExecuteMsg, Extension, QueryMsg as Cw721QueryMsg};
+
+use crate::allowlist::check_allowed;
+use crate::contract::{sale_payout, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::listing::listing_approved;
+use crate::msg::{ListRental, Rent};
+use crate::package::{Price, QueryRentalResult, RentalsResponse};
+use crate::state::{increment_rentals, Rental, RENTALS};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * List an nft for rent. The sender must own it and have approved the marketplace, which sets
+ * the renter as its user, the nft never leaves the owner wallet
+ */
+pub fn try_list_rental(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ListRental,
+) -> Result<Response, ContractError> {
+    let contract_addr = deps.api.addr_validate(&msg.contract_addr)?;
+    let owner: OwnerOfResponse = deps.querier.query_wasm_smart(
+        &contract_addr,
+        &Cw721QueryMsg::OwnerOf {
+            token_id: msg.token_id.clone(),
+            include_expired: Some(false),
+        },
+    )?;
+    if owner.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !listing_approved(deps.as_ref(), &env, &contract_addr, &msg.token_id, &info.sender)? {
+        return Err(ContractError::NotApproved {});
+    }
+    let price_per_period = msg.price_per_period.validate(deps.api)?;
+    if msg.period == 0 || msg.max_periods == 0 || price_per_period.amount().is_zero() {
+        return Err(ContractError::InvalidRental {});
+    }
+    check_allowed(deps.as_ref(), &contract_addr, &price_per_period)?;
+
+    let id = increment_rentals(deps.storage)?.to_string();
+    let rental = Rental {
+        owner: info.sender,
+        contract_addr,
+        token_id: msg.token_id,
+        price_per_period,
+        period: msg.period,
+        max_periods: msg.max_periods,
+        renter: None,
+        rented_until: None,
+    };
+    RENTALS.save(deps.storage, &id, &rental)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "list_rental")
+        .add_attribute("rental_id", id)
+        .add_attribute("owner", rental.owner)
+        .add_attribute("contract_addr", rental.contract_addr)
+        .add_attribute("token_id", rental.token_id)
+        .add_attribute("price_per_period", rental.price_per_period.to_string())
+        .add_attribute("period", rental.period.to_string())
+    )
+}
+
+/**
+ * Remove a rental listing, owner only. A current renter stays the nft user until it expires
+ */
+pub fn try_cancel_rental(
+    deps: DepsMut,
+    info: MessageInfo,
+    rental_id: String,
+) -> Result<Response, ContractError> {
+    let rental = RENTALS.load(deps.storage, &rental_id)?;
+    if rental.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    RENTALS.remove(deps.storage, &rental_id);
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_rental")
+        .add_attribute("rental_id", rental_id)
+        .add_attribute("owner", rental.owner)
+    )
+}
+
+/**
+ * Rent an nft listed in a native coin, paying with the funds attached to the message
+ */
+pub fn try_rent(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Rent,
+) -> Result<Response, ContractError> {
+    let rental = RENTALS.load(deps.storage, &msg.rental_id)?;
+    let paid = match &rental.price_per_period {
+        Price::Native(coin) => must_pay(&info, &coin.denom)?,
+        Price::Cw20(_) => return Err(ContractError::Cw20PriceOnly {}),
+    };
+    settle_rental(deps, env, msg, rental, info.sender, paid)
+}
+
+/**
+ * Rent an nft listed in a CW20 token with the tokens sent through the `Send` hook
+ */
+pub fn try_receive_rent(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: Rent,
+) -> Result<Response, ContractError> {
+    let rental = RENTALS.load(deps.storage, &msg.rental_id)?;
+    match &rental.price_per_period {
+        Price::Cw20(coin) if coin.address != info.sender => {
+            return Err(ContractError::WrongToken {
+                expected: coin.address.to_string(),
+                received: info.sender.to_string(),
+            })
+        }
+        Price::Cw20(_) => {}
+        Price::Native(_) => return Err(ContractError::NativePriceOnly {}),
+    }
+    let renter = deps.api.addr_validate(&rcv_msg.sender)?;
+    settle_rental(deps, env, msg, rental, renter, rcv_msg.amount)
+}
+
+/**
+ * Charge `periods` periods of a rental, paid to the owner with the fee and royalty taken like on
+ * a sale, and make the renter the nft user until the end of the last period
+ */
+fn settle_rental(
+    deps: DepsMut,
+    env: Env,
+    msg: Rent,
+    mut rental: Rental,
+    renter: Addr,
+    paid: Uint128,
+) -> Result<Response, ContractError> {
+    if let Some(until) = rental.rented_until.filter(|until| *until > env.block.time) {
+        return Err(ContractError::AlreadyRented { until });
+    }
+    if msg.periods == 0 || msg.periods > rental.max_periods {
+        return Err(ContractError::InvalidPeriods { max: rental.max_periods });
+    }
+    if !listing_approved(deps.as_ref(), &env, &rental.contract_addr, &rental.token_id, &rental.owner)? {
+        return Err(ContractError::StaleListing {});
+    }
+    let price = rental.price_per_period.amount() * Uint128::from(msg.periods);
+    if paid < price {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    let until = env.block.time.plus_seconds(rental.period * u64::from(msg.periods));
+    rental.renter = Some(renter.clone());
+    rental.rented_until = Some(until);
+    RENTALS.save(deps.storage, &msg.rental_id, &rental)?;
+
+    let payout = sale_payout(deps.as_ref(), &rental.price_per_period, price, &rental.contract_addr, &rental.token_id, &rental.owner)?;
+    let mut messages = payout.messages;
+    if paid > price {
+        messages.push(rental.price_per_period.transfer_msg(&renter, paid - price)?);
+    }
+    messages.push(set_user_msg(&rental.contract_addr, &rental.token_id, &renter, until)?);
+
+    Ok(Response::new()
+        .add_attribute("action", "rent")
+        .add_attribute("rental_id", msg.rental_id)
+        .add_attribute("renter", renter)
+        .add_attribute("owner", rental.owner)
+        .add_attribute("paid_price", rental.price_per_period.with_amount(price).to_string())
+        .add_attribute("fee", payout.fee)
+        .add_attribute("royalty", payout.royalty)
+        .add_attribute("rented_until", until.to_string())
+        .add_messages(messages)
+    )
+}
+
+pub(crate) fn set_user_msg(contract_addr: &Addr, token_id: &str, user: &Addr, until: Timestamp) -> StdResult<CosmosMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: contract_addr.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::<Extension>::SetUser {
+            token_id: token_id.to_string(),
+            user: user.to_string(),
+            expires: Expiration::AtTime(until),
+        })?,
+        funds: vec![],
+    }
+    .into())
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_rental(deps: Deps, env: Env, rental_id: String) -> StdResult<QueryRentalResult> {
+    let rental = RENTALS.load(deps.storage, &rental_id)?;
+    Ok(parse_rental(rental_id, rental, env.block.time))
+}
+
+pub fn query_rentals(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RentalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let rentals = RENTALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, rental)| parse_rental(id, rental, env.block.time)))
+        .collect::<StdResult<_>>()?;
+    Ok(RentalsResponse { rentals })
+}
+
+fn parse_rental(id: String, rental: Rental, now: Timestamp) -> QueryRentalResult {
+    // an expired rental leaves the nft free to rent
+    let rented = rental.rented_until.map_or(false, |until| until > now);
+    QueryRentalResult {
+        id,
+        owner: rental.owner,
+        contract_addr: rental.contract_addr,
+        token_id: rental.token_id,
+        price_per_period: rental.price_per_period,
+        period: rental.period,
+        max_periods: rental.max_periods,
+        renter: rental.renter.filter(|_| rented),
+        rented_until: rental.rented_until.filter(|_| rented),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market};
+    use crate::contract::{execute, query};
+    use crate::listing::tests::mock_nft;
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, BankMsg};
+
+    fn list_msg() -> HandleMsg {
+        list_msg_in(Price::Native(coin(10, "ujuno")))
+    }
+
+    fn list_msg_in(price_per_period: Price) -> HandleMsg {
+        HandleMsg::ListRental(ListRental {
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from("Sword"),
+            price_per_period,
+            period: 3600,
+            max_periods: 24,
+        })
+    }
+
+    fn rent(periods: u32) -> HandleMsg {
+        HandleMsg::Rent(Rent { rental_id: String::from("1"), periods })
+    }
+
+    fn receive_rent(rental_id: &str, amount: u128, periods: u32) -> HandleMsg {
+        HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("renter"),
+            amount: Uint128::new(amount),
+            msg: to_binary(&ReceiveMsg::Rent(Rent { rental_id: rental_id.to_string(), periods })).unwrap(),
+        })
+    }
+
+    #[test]
+    fn rental_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_nft(&mut deps, "owner", true, false);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), list_msg()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), list_msg()).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("renter", &coins(250, "ujuno")), rent(25)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPeriods { max: 24 }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("renter", &coins(20, "ujuno")), rent(3)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+
+        // the owner is paid and the renter becomes the nft user for 3 periods
+        let res = execute(deps.as_mut(), mock_env(), mock_info("renter", &coins(35, "ujuno")), rent(3)).unwrap();
+        let until = mock_env().block.time.plus_seconds(3 * 3600);
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "owner".to_string(), amount: coins(30, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "renter".to_string(), amount: coins(5, "ujuno") }),
+                set_user_msg(&Addr::unchecked("nftcontract"), "Sword", &Addr::unchecked("renter"), until).unwrap(),
+            ]
+        );
+
+        // nobody else can rent it until the rental expires
+        let err = execute(deps.as_mut(), mock_env(), mock_info("other", &coins(10, "ujuno")), rent(1)).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyRented { .. }));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Rental { rental_id: String::from("1") }).unwrap();
+        let rental: QueryRentalResult = from_binary(&res).unwrap();
+        assert_eq!((rental.renter, rental.rented_until), (Some(Addr::unchecked("renter")), Some(until)));
+
+        let mut later = mock_env();
+        later.block.time = until;
+        execute(deps.as_mut(), later, mock_info("other", &coins(10, "ujuno")), rent(1)).unwrap();
+    }
+
+    #[test]
+    fn cw20_rental_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_nft(&mut deps, "owner", true, false);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), list_msg_in(cw20_price(10))).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), list_msg()).unwrap();
+
+        // each rental is paid in its own currency only
+        let err = execute(deps.as_mut(), mock_env(), mock_info("renter", &coins(30, "ujuno")), rent(3)).unwrap_err();
+        assert!(matches!(err, ContractError::Cw20PriceOnly {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive_rent("2", 30, 3)).unwrap_err();
+        assert!(matches!(err, ContractError::NativePriceOnly {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), receive_rent("1", 30, 3)).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive_rent("1", 20, 3)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+
+        // the owner is paid in the token and the overpayment refunded
+        let res = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive_rent("1", 35, 3)).unwrap();
+        let until = mock_env().block.time.plus_seconds(3 * 3600);
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                cw20_price(0).transfer_msg(&Addr::unchecked("owner"), Uint128::new(30)).unwrap(),
+                cw20_price(0).transfer_msg(&Addr::unchecked("renter"), Uint128::new(5)).unwrap(),
+                set_user_msg(&Addr::unchecked("nftcontract"), "Sword", &Addr::unchecked("renter"), until).unwrap(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use bech32::{ToBase32, Variant};
+use cosmwasm_std::{to_vec, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::must_pay;
+use ripemd160::Ripemd160;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::allowlist::check_allowed;
+use crate::contract::{sale_payout, transfer_nft_msg};
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::listing::listing_approved;
+use crate::msg::BuySigned;
+use crate::package::{NonceUsedResponse, Price};
+use crate::state::USED_NONCES;
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Buy a SignedOrder priced in a native coin, paying with the funds attached to the message
+ */
+pub fn try_buy_signed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: BuySigned,
+) -> Result<Response, ContractError> {
+    let paid = match &msg.order.price {
+        Price::Native(coin) => must_pay(&info, &coin.denom)?,
+        Price::Cw20(_) => return Err(ContractError::Cw20PriceOnly {}),
+    };
+    settle_signed(deps, env, msg, info.sender, paid)
+}
+
+/**
+ * Buy a SignedOrder priced in a CW20 token with the tokens sent through the `Send` hook
+ */
+pub fn try_receive_buy_signed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: BuySigned,
+) -> Result<Response, ContractError> {
+    match &msg.order.price {
+        Price::Cw20(coin) if coin.address != info.sender => {
+            return Err(ContractError::WrongToken {
+                expected: coin.address.to_string(),
+                received: info.sender.to_string(),
+            })
+        }
+        Price::Cw20(_) => {}
+        Price::Native(_) => return Err(ContractError::NativePriceOnly {}),
+    }
+    let buyer = deps.api.addr_validate(&rcv_msg.sender)?;
+    settle_signed(deps, env, msg, buyer, rcv_msg.amount)
+}
+
+/**
+ * Spend nonces of the sender so that its signed orders using them can't be bought anymore
+ */
+pub fn try_cancel_signed_orders(
+    deps: DepsMut,
+    info: MessageInfo,
+    nonces: Vec<u64>,
+) -> Result<Response, ContractError> {
+    for nonce in &nonces {
+        USED_NONCES.save(deps.storage, (&info.sender, *nonce), &Empty {})?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_signed_orders")
+        .add_attribute("seller", info.sender)
+        .add_attribute("nonces", nonces.iter().map(u64::to_string).collect::<Vec<_>>().join(","))
+    )
+}
+
+/**
+ * Sell the nft of a SignedOrder once the payment is held by the marketplace. The order must be
+ * signed by the key of the seller account and its nonce unused, the nft is transferred from the
+ * seller wallet with the marketplace approval, usually a standing `ApproveAll`
+ */
+fn settle_signed(
+    deps: DepsMut,
+    env: Env,
+    msg: BuySigned,
+    buyer: Addr,
+    paid: Uint128,
+) -> Result<Response, ContractError> {
+    let order = &msg.order;
+    let seller = deps.api.addr_validate(&order.seller)?;
+    let contract_addr = deps.api.addr_validate(&order.contract_addr)?;
+    if order.expires.is_expired(&env.block) {
+        return Err(ContractError::OfferingExpired {});
+    }
+    if USED_NONCES.has(deps.storage, (&seller, order.nonce)) {
+        return Err(ContractError::NonceUsed { nonce: order.nonce });
+    }
+
+    verify_signature(deps.as_ref(), &env, order, &msg.signature, &msg.pubkey, &seller)?;
+
+    let price = order.price.clone().validate(deps.api)?;
+    check_allowed(deps.as_ref(), &contract_addr, &price)?;
+    if !listing_approved(deps.as_ref(), &env, &contract_addr, &order.token_id, &seller)? {
+        return Err(ContractError::StaleListing {});
+    }
+    let amount = price.amount();
+    if paid < amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    USED_NONCES.save(deps.storage, (&seller, order.nonce), &Empty {})?;
+    record_sale(deps.storage, &env, &contract_addr, &order.token_id, &seller, &buyer, price.clone())?;
+
+    let payout = sale_payout(deps.as_ref(), &price, amount, &contract_addr, &order.token_id, &seller)?;
+    let mut cosmos_msgs = payout.messages;
+    if paid > amount {
+        cosmos_msgs.push(price.transfer_msg(&buyer, paid - amount)?);
+    }
+    cosmos_msgs.push(transfer_nft_msg(&contract_addr, &buyer, &order.token_id)?);
+
+    Ok(Response::new()
+        .add_attribute("action", "buy_signed")
+        .add_attribute("buyer", buyer)
+        .add_attribute("seller", seller)
+        .add_attribute("nonce", order.nonce.to_string())
+        .add_attribute("paid_price", price.to_string())
+        .add_attribute("fee", payout.fee)
+        .add_attribute("royalty", payout.royalty)
+        .add_attribute("token_id", order.token_id.clone())
+        .add_attribute("contract_addr", contract_addr)
+        .add_messages(cosmos_msgs)
+    )
+}
+
+/**
+ * Check that `payload` was signed by the secp256k1 key of the `signer` account, `pubkey` being
+ * the compressed public key of that key
+ */
+pub(crate) fn verify_signature<T: Serialize>(
+    deps: Deps,
+    env: &Env,
+    payload: &T,
+    signature: &Binary,
+    pubkey: &Binary,
+    signer: &Addr,
+) -> Result<(), ContractError> {
+    let digest = signed_digest(&env.contract.address, payload)?;
+    let verified = deps.api.secp256k1_verify(&digest, signature, pubkey).unwrap_or(false);
+    let (prefix, _, _) = bech32::decode(signer.as_str()).map_err(|_| ContractError::InvalidSignature {})?;
+    if !verified || pubkey_address(pubkey, &prefix)? != *signer {
+        return Err(ContractError::InvalidSignature {});
+    }
+    Ok(())
+}
+
+/**
+ * The sha256 hash signed off-chain for a SignedOrder or a MintVoucher. It starts with the
+ * marketplace address so that a signature can't be replayed on another marketplace instance
+ */
+pub fn signed_digest<T: Serialize>(marketplace: &Addr, payload: &T) -> StdResult<Vec<u8>> {
+    Ok(Sha256::digest(&signed_preimage(marketplace, payload)?).to_vec())
+}
+
+pub(crate) fn signed_preimage<T: Serialize>(marketplace: &Addr, payload: &T) -> StdResult<Vec<u8>> {
+    let mut preimage = marketplace.as_bytes().to_vec();
+    preimage.extend(to_vec(payload)?);
+    Ok(preimage)
+}
+
+/**
+ * The account address of a compressed secp256k1 public key with the bech32 `prefix` of the chain,
+ * derived the way cosmos sdk chains do
+ */
+pub(crate) fn pubkey_address(pubkey: &[u8], prefix: &str) -> Result<Addr, ContractError> {
+    let hash = Ripemd160::digest(&Sha256::digest(pubkey));
+    let address = bech32::encode(prefix, hash.to_base32(), Variant::Bech32)
+        .map_err(|_| ContractError::InvalidSignature {})?;
+    Ok(Addr::unchecked(address))
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_nonce_used(deps: Deps, seller: String, nonce: u64) -> StdResult<NonceUsedResponse> {
+    let seller = deps.api.addr_validate(&seller)?;
+    let used = USED_NONCES.has(deps.storage, (&seller, nonce));
+    Ok(NonceUsedResponse { used })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market};
+    use crate::contract::{execute, query};
+    use crate::listing::tests::mock_nft;
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg, SignedOrder};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, to_binary, BankMsg, CosmosMsg};
+    use cw_utils::Expiration;
+    use k256::ecdsa::signature::DigestSigner;
+    use k256::ecdsa::{Signature, SigningKey};
+
+    /**
+     * Sign `payload` for the mock marketplace, returns the signature and the compressed public key
+     */
+    pub(crate) fn sign<T: Serialize>(key: &SigningKey, payload: &T) -> (Binary, Binary) {
+        let preimage = signed_preimage(&mock_env().contract.address, payload).unwrap();
+        let signature: Signature = key.sign_digest(Sha256::new().chain(&preimage));
+        (Binary::from(signature.as_ref()), Binary::from(key.verifying_key().to_bytes().as_slice()))
+    }
+
+    // the juno account of a key, leaked to be usable in the mocked queriers
+    pub(crate) fn key_address(key: &SigningKey) -> &'static str {
+        let address = pubkey_address(key.verifying_key().to_bytes().as_slice(), "juno").unwrap();
+        Box::leak(address.into_string().into_boxed_str())
+    }
+
+    fn signed_order(key: &SigningKey, seller: &str, nonce: u64) -> BuySigned {
+        signed_order_in(key, seller, nonce, Price::Native(coin(100, "ujuno")))
+    }
+
+    fn signed_order_in(key: &SigningKey, seller: &str, nonce: u64, price: Price) -> BuySigned {
+        let order = SignedOrder {
+            seller: seller.to_string(),
+            contract_addr: String::from("nftcontract"),
+            token_id: String::from("Gasless"),
+            price,
+            expires: Expiration::AtHeight(mock_env().block.height + 100),
+            nonce,
+        };
+        let (signature, pubkey) = sign(key, &order);
+        BuySigned { order, signature, pubkey }
+    }
+
+    #[test]
+    fn signed_order_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let key = SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let seller = key_address(&key);
+        mock_nft(&mut deps, seller, false, true);
+        let buy = |msg: BuySigned| HandleMsg::BuySigned(msg);
+
+        // an order signed by another key or tampered with is refused
+        let other = SigningKey::from_bytes(&[8u8; 32]).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy(signed_order(&other, seller, 1))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSignature {}));
+        let mut tampered = signed_order(&key, seller, 1);
+        tampered.order.price = Price::Native(coin(1, "ujuno"));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(1, "ujuno")), buy(tampered)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSignature {}));
+
+        // the nft leaves the seller wallet through its ApproveAll
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(120, "ujuno")), buy(signed_order(&key, seller, 1))).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: seller.to_string(), amount: coins(100, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "buyer".to_string(), amount: coins(20, "ujuno") }),
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Gasless").unwrap(),
+            ]
+        );
+
+        // the order can't be replayed and a cancelled nonce can't be used
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy(signed_order(&key, seller, 1))).unwrap_err();
+        assert!(matches!(err, ContractError::NonceUsed { nonce: 1 }));
+        let cancel = HandleMsg::CancelSignedOrders { nonces: vec![2] };
+        execute(deps.as_mut(), mock_env(), mock_info(seller, &[]), cancel).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy(signed_order(&key, seller, 2))).unwrap_err();
+        assert!(matches!(err, ContractError::NonceUsed { nonce: 2 }));
+
+        let msg = QueryMsg::NonceUsed { seller: seller.to_string(), nonce: 3 };
+        let res: NonceUsedResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert!(!res.used);
+    }
+
+    #[test]
+    fn cw20_signed_order_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let key = SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let seller = key_address(&key);
+        mock_nft(&mut deps, seller, false, true);
+        let receive = |amount: u128, msg: BuySigned| {
+            HandleMsg::Receive(Cw20ReceiveMsg {
+                sender: String::from("buyer"),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::BuySigned(msg)).unwrap(),
+            })
+        };
+
+        // each order is paid in its own currency only
+        let buy = HandleMsg::BuySigned(signed_order_in(&key, seller, 1, cw20_price(100)));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(100, "ujuno")), buy).unwrap_err();
+        assert!(matches!(err, ContractError::Cw20PriceOnly {}));
+        let native = receive(100, signed_order(&key, seller, 1));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), native).unwrap_err();
+        assert!(matches!(err, ContractError::NativePriceOnly {}));
+        let spoofed = receive(100, signed_order_in(&key, seller, 1, cw20_price(100)));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), spoofed).unwrap_err();
+        assert!(matches!(err, ContractError::WrongToken { .. }));
+        let short = receive(90, signed_order_in(&key, seller, 1, cw20_price(100)));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), short).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+
+        // the seller is paid in the token and the nonce spent
+        let res = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive(100, signed_order_in(&key, seller, 1, cw20_price(100)))).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                cw20_price(0).transfer_msg(&Addr::unchecked(seller), Uint128::new(100)).unwrap(),
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Gasless").unwrap(),
+            ]
+        );
+        let replay = receive(100, signed_order_in(&key, seller, 1, cw20_price(100)));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), replay).unwrap_err();
+        assert!(matches!(err, ContractError::NonceUsed { nonce: 1 }));
+    }
+}
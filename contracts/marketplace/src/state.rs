@@ -1,17 +1,19 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 #![allow(unused_imports)]
-use crate::package::ContractInfoResponse;
+use crate::package::{ContractInfoResponse, Price};
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::ops::Deref;
 use std::str::{from_utf8, FromStr};
 
-use cosmwasm_std::{Addr, CanonicalAddr, StdResult, Storage, MemoryStorage};
+use cosmwasm_std::{Addr, CanonicalAddr, Empty, StdResult, Storage, MemoryStorage, Timestamp, Uint128};
 //use cosmwasm_std::testing::MockStorage;
 use cw20::Cw20CoinVerified;
-use cw_storage_plus::{index_string, Index, IndexList, IndexedMap, Item, Map, MultiIndex, KeyDeserialize};
+use cw_storage_plus::{index_string, Index, IndexList, IndexedMap, Item, Map, MultiIndex, KeyDeserialize, PrimaryKey, UniqueIndex};
+use cw_utils::Expiration;
 
 pub static CONFIG_KEYS: &[u8] = b"config";
 // @{Deprecated} State sample code
@@ -21,6 +23,31 @@ pub struct State {
     pub owner: Addr,
 }
 
+/**
+ * Config holds the marketplace admin and the protocol fee taken from each sale,
+ * `fee_bps` is expressed in basis points and paid to `fee_recipient`
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub admin: Addr,
+    pub fee_bps: u64,
+    pub fee_recipient: Addr,
+    // any collection and currency can be listed in open mode, otherwise only the allow-listed ones
+    #[serde(default = "default_open_mode")]
+    pub open_mode: bool,
+    // admin proposed by the current one, becomes admin once it accepts
+    #[serde(default)]
+    pub pending_admin: Option<Addr>,
+    // no new listing or sale while paused, sellers and bidders can still get their assets back
+    #[serde(default)]
+    pub paused: bool,
+}
+
+// marketplaces deployed before the allow-lists were permissionless
+fn default_open_mode() -> bool {
+    true
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  * Offering<T> offer the struct of Offer will list on marketplace
@@ -35,10 +62,249 @@ pub struct Offering<T> {
     pub token_id: String,
     pub contract_addr: Addr,
     pub seller: Addr,
-    pub list_price: Cw20CoinVerified,
+    pub list_price: Price,
+    // descending price schedule of a dutch listing, `list_price` is then the start price
+    pub price_decline: Option<PriceDecline>,
+    // offerings listed before expiration existed never expire
+    #[serde(default)]
+    pub expires: Expiration,
+    // the nft stays in the seller wallet, the marketplace only holds an approval on it
+    #[serde(default)]
+    pub non_custodial: bool,
+    // a private listing only `reserved_for` can buy
+    #[serde(default)]
+    pub reserved_for: Option<Addr>,
     pub extension: T 
 }
 
+/**
+ * Offering as stored before 0.2.0, priced in a CW20 token only
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyOffering {
+    pub owner: Addr,
+    pub token_id: String,
+    pub contract_addr: Addr,
+    pub seller: Addr,
+    pub list_price: Cw20CoinVerified,
+    pub extension: String,
+}
+
+// any record found under the offerings namespace while migrating
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum StoredOffering {
+    Current(Offering<String>),
+    Legacy(LegacyOffering),
+}
+
+impl From<StoredOffering> for Offering<String> {
+    fn from(stored: StoredOffering) -> Self {
+        match stored {
+            StoredOffering::Current(off) => off,
+            StoredOffering::Legacy(off) => Offering {
+                owner: off.owner,
+                token_id: off.token_id,
+                contract_addr: off.contract_addr,
+                seller: off.seller,
+                list_price: Price::Cw20(off.list_price),
+                price_decline: None,
+                expires: Expiration::Never {},
+                non_custodial: false,
+                reserved_for: None,
+                extension: off.extension,
+            },
+        }
+    }
+}
+
+/**
+ * PriceDecline makes an Offering a dutch listing: the price falls from `list_price`
+ * to `floor_price` between `start_time` and `end_time`, continuously or every `step_interval`
+ * seconds, and stays at `floor_price` afterwards
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceDecline {
+    pub floor_price: Uint128,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub step_interval: Option<u64>,
+}
+
+impl<T> Offering<T> {
+    // price to pay for the Offering at `now`
+    pub fn current_price(&self, now: Timestamp) -> Uint128 {
+        let start_price = self.list_price.amount();
+        let decline = match &self.price_decline {
+            Some(decline) => decline,
+            None => return start_price,
+        };
+        if now <= decline.start_time {
+            return start_price;
+        }
+        if now >= decline.end_time {
+            return decline.floor_price;
+        }
+        let duration = decline.end_time.seconds() - decline.start_time.seconds();
+        let mut elapsed = now.seconds() - decline.start_time.seconds();
+        if let Some(step) = decline.step_interval {
+            elapsed -= elapsed % step;
+        }
+        start_price - (start_price - decline.floor_price).multiply_ratio(elapsed, duration)
+    }
+}
+
+/**
+ * Auction is an English auction of an nft held by the marketplace. Bids are escrowed in the
+ * currency of `reserve_price`, the previous bidder is refunded as soon as it is outbid
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Auction {
+    pub seller: Addr,
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub reserve_price: Price,
+    pub end_time: Timestamp,
+    pub min_increment: Uint128,
+    pub highest_bid: Option<Bid>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bid {
+    pub bidder: Addr,
+    pub amount: Uint128,
+}
+
+impl Auction {
+    // smallest amount accepted for the next bid
+    pub fn min_bid(&self) -> Uint128 {
+        match &self.highest_bid {
+            Some(bid) => bid.amount + self.min_increment.max(Uint128::new(1)),
+            None => Uint128::new(1),
+        }
+    }
+}
+
+/**
+ * Bundle sells several nfts for one price. It is a draft while the seller adds nfts to it,
+ * sent to the marketplace or approved to it, and is for sale once `list_price` is set
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bundle {
+    pub seller: Addr,
+    pub items: Vec<BundleItem>,
+    pub list_price: Option<Price>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BundleItem {
+    pub contract_addr: Addr,
+    pub token_id: String,
+    // the nft stays in the seller wallet, the marketplace only holds an approval on it
+    pub non_custodial: bool,
+}
+
+/**
+ * Rental lists an nft kept in the owner wallet for rent. Renting it sets the renter as the nft
+ * user until `rented_until`, the owner can't revoke it before
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Rental {
+    pub owner: Addr,
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub price_per_period: Price,
+    // length of a period in seconds
+    pub period: u64,
+    pub max_periods: u32,
+    pub renter: Option<Addr>,
+    pub rented_until: Option<Timestamp>,
+}
+
+/**
+ * Swap trades the `offered` nfts of `maker`, and its escrowed `sweetener`, for the `wanted` nfts
+ * of `counterparty`. Both sides are released together once every nft has been deposited
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Swap {
+    pub maker: Addr,
+    pub counterparty: Addr,
+    pub offered: Vec<SwapItem>,
+    pub wanted: Vec<SwapItem>,
+    pub sweetener: Option<Price>,
+}
+
+impl Swap {
+    pub fn complete(&self) -> bool {
+        self.offered.iter().chain(&self.wanted).all(|item| item.deposited)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapItem {
+    pub contract_addr: Addr,
+    pub token_id: String,
+    // held by the marketplace
+    pub deposited: bool,
+}
+
+/**
+ * Offer is a buyer bid on any nft, listed or not. The price is escrowed by the marketplace
+ * until the owner accepts it, the bidder cancels it or it expires
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Offer {
+    pub bidder: Addr,
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub price: Price,
+    pub expires: Expiration,
+}
+
+/**
+ * CollectionOffer is a bid on any nft of a collection, for `quantity` tokens at `price` each.
+ * The marketplace escrows `price * quantity` and pays out each fill from it
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CollectionOffer {
+    pub bidder: Addr,
+    pub contract_addr: Addr,
+    pub price: Price,
+    pub quantity: u32,
+    pub expires: Expiration,
+}
+
+impl CollectionOffer {
+    // escrow left for the remaining quantity
+    pub fn escrow(&self) -> Uint128 {
+        self.price.amount() * Uint128::from(self.quantity)
+    }
+}
+
+/**
+ * Trade records a completed sale, whatever path it went through
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Trade {
+    pub contract_addr: Addr,
+    pub token_id: String,
+    pub seller: Addr,
+    pub buyer: Addr,
+    pub price: Price,
+    pub time: Timestamp,
+}
+
+/**
+ * CollectionStats aggregates the trades of a collection in one currency
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct CollectionStats {
+    pub volume: Uint128,
+    pub sales: u64,
+    pub last_sale: Uint128,
+    pub highest_sale: Uint128,
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  * Trait define private using in scope of crate state only
@@ -83,10 +349,51 @@ where T: Serialize + DeserializeOwned + Clone,
 
 // @{Deprecated} STATE  
 pub const STATE: Item<State> = Item::new("state");
-// OFFERINGS is a map which maps the offering_id to an offering. Offering_id is derived from OFFERINGS_COUNT
-pub const OFFERINGS: Map<&str, Offering<String>> = Map::new("offerings" as &str);
+// offerings() maps the offering_id to an offering. Offering_id is derived from OFFERINGS_COUNT
 pub const OFFERINGS_COUNT: Item<u64> = Item::new("num_offerings" as &str);
 pub const CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("marketplace_info" as &str);
+pub const CONFIG: Item<Config> = Item::new("config" as &str);
+// AUCTIONS maps the auction_id to an Auction, auction_id is derived from AUCTIONS_COUNT
+pub const AUCTIONS: Map<&str, Auction> = Map::new("auctions" as &str);
+pub const AUCTIONS_COUNT: Item<u64> = Item::new("num_auctions" as &str);
+// BUNDLES maps the bundle_id to a Bundle, bundle_id is derived from BUNDLES_COUNT
+pub const BUNDLES: Map<&str, Bundle> = Map::new("bundles" as &str);
+pub const BUNDLES_COUNT: Item<u64> = Item::new("num_bundles" as &str);
+// BUNDLED_ITEMS maps the (nft contract, token_id) of every bundle item to its bundle_id
+pub const BUNDLED_ITEMS: Map<(&Addr, &str), String> = Map::new("bundled_items" as &str);
+// RENTALS maps the rental_id to a Rental, rental_id is derived from RENTALS_COUNT
+pub const RENTALS: Map<&str, Rental> = Map::new("rentals" as &str);
+pub const RENTALS_COUNT: Item<u64> = Item::new("num_rentals" as &str);
+// SWAPS maps the swap_id to a Swap, swap_id is derived from SWAPS_COUNT
+pub const SWAPS: Map<&str, Swap> = Map::new("swaps" as &str);
+pub const SWAPS_COUNT: Item<u64> = Item::new("num_swaps" as &str);
+pub const OFFERS_COUNT: Item<u64> = Item::new("num_offers" as &str);
+// allow-lists of nft contracts and currencies (native denom or CW20 address) used outside open mode
+pub const ALLOWED_COLLECTIONS: Map<&Addr, Empty> = Map::new("allowed_collections" as &str);
+pub const ALLOWED_CURRENCIES: Map<&str, Empty> = Map::new("allowed_currencies" as &str);
+pub const COLLECTION_OFFERS_COUNT: Item<u64> = Item::new("num_collection_offers" as &str);
+pub const TRADES_COUNT: Item<u64> = Item::new("num_trades" as &str);
+// USED_NONCES holds the (seller, nonce) of every signed order bought or cancelled
+pub const USED_NONCES: Map<(&Addr, u64), Empty> = Map::new("used_nonces" as &str);
+// LAZY_MINT_CREATORS maps an nft contract minted by the marketplace to the creator signing its vouchers
+pub const LAZY_MINT_CREATORS: Map<&Addr, Addr> = Map::new("lazy_mint_creators" as &str);
+// REDEEMED_VOUCHERS holds the digest of every MintVoucher redeemed
+pub const REDEEMED_VOUCHERS: Map<&[u8], Empty> = Map::new("redeemed_vouchers" as &str);
+// COLLECTION_STATS maps (nft contract, currency) to the CollectionStats of its trades
+pub const COLLECTION_STATS: Map<(&Addr, &str), CollectionStats> = Map::new("collection_stats" as &str);
+
+// a bundle is bought in a single transaction, keep its transfers bounded
+pub const MAX_BUNDLE_ITEMS: usize = 25;
+
+// same bound for the Offerings bought by a single BuyMany
+pub const MAX_SWEEP_OFFERINGS: usize = 25;
+
+// and for each side of a swap
+pub const MAX_SWAP_ITEMS: usize = 25;
+
+// fee_bps is expressed over BPS_DENOMINATOR and capped at MAX_FEE_BPS, 10% of the sale price
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_FEE_BPS: u64 = 1_000;
 
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
@@ -113,6 +420,55 @@ pub fn increment_offerings(storage: &mut dyn Storage) -> StdResult<u64> {
     Ok(val)
 }
 
+pub fn increment_auctions(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = AUCTIONS_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    AUCTIONS_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+/**
+ * A MultiIndex holding only the items matching `filter`, queries on it don't have to skip the
+ * others. Derefs to the MultiIndex for the queries
+ */
+pub struct FilteredIndex<'a, IK, T> {
+    filter: fn(&T) -> bool,
+    index: MultiIndex<'a, IK, T, String>,
+}
+
+impl<'a, IK, T> FilteredIndex<'a, IK, T> {
+    pub fn new(filter: fn(&T) -> bool, index: MultiIndex<'a, IK, T, String>) -> Self {
+        FilteredIndex { filter, index }
+    }
+}
+
+impl<'a, IK, T> Deref for FilteredIndex<'a, IK, T> {
+    type Target = MultiIndex<'a, IK, T, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.index
+    }
+}
+
+impl<'a, IK, T> Index<T> for FilteredIndex<'a, IK, T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    IK: PrimaryKey<'a>,
+{
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &T) -> StdResult<()> {
+        match (self.filter)(data) {
+            true => self.index.save(store, pk, data),
+            false => Ok(()),
+        }
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &T) -> StdResult<()> {
+        match (self.filter)(old_data) {
+            true => self.index.remove(store, pk, old_data),
+            false => Ok(()),
+        }
+    }
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  * Indexes Struct of Offering to ref Addr to Offering<T> by Addr to Storage
@@ -120,9 +476,18 @@ pub fn increment_offerings(storage: &mut dyn Storage) -> StdResult<u64> {
 pub struct OfferingIndexes<'a, T> 
 where T: Serialize + DeserializeOwned + Clone,
 {
-    pub owner: MultiIndex<'a, Addr, Offering<T>, Addr>,
-    pub seller: MultiIndex<'a, Addr, Offering<T>, Addr>,
-    pub contract: MultiIndex<'a, Addr, Offering<T>, Addr>,
+    pub owner: MultiIndex<'a, Addr, Offering<T>, String>,
+    pub seller: MultiIndex<'a, Addr, Offering<T>, String>,
+    pub contract: MultiIndex<'a, Addr, Offering<T>, String>,
+    // (contract_addr, currency, list price amount) of the public fixed price offerings, sorts the
+    // listings of a collection by price
+    pub price: FilteredIndex<'a, (Addr, String, u128), Offering<T>>,
+    // (contract_addr, currency) of the public dutch listings, whose price isn't fixed
+    pub dutch: FilteredIndex<'a, (Addr, String), Offering<T>>,
+    // (contract_addr, token_id), a token can only have one offering
+    pub token: UniqueIndex<'a, (Addr, String), Offering<T>, String>,
+    // buyer of a private listing, public listings aren't indexed
+    pub reserved: FilteredIndex<'a, Addr, Offering<T>>,
 }
 
 
@@ -135,7 +500,7 @@ where
     T: Serialize + DeserializeOwned + Clone,
 {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Offering<T>>> + '_> {
-        let v: Vec<&dyn Index<Offering<T>>> = vec![&self.owner, &self.seller, &self.contract];
+        let v: Vec<&dyn Index<Offering<T>>> = vec![&self.owner, &self.seller, &self.contract, &self.price, &self.dutch, &self.token, &self.reserved];
         Box::new(v.into_iter())
 
     }
@@ -153,7 +518,7 @@ pub fn offering_owner_idx<T>(d: &Offering<T>) -> Addr {
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  * Storage function used to build IndexedMap
  */
-pub fn offerings<'a, T, S: Storage> () -> IndexedMap<'a, &'a str, Offering<T>, OfferingIndexes<'a, T>> 
+pub fn offerings<'a, T> () -> IndexedMap<'a, &'a str, Offering<T>, OfferingIndexes<'a, T>> 
 where T: Serialize + DeserializeOwned + Clone,
 {
     let indexes: OfferingIndexes<'a, T> = OfferingIndexes {
@@ -172,10 +537,162 @@ where T: Serialize + DeserializeOwned + Clone,
             "offerings",
             "offerings_contract",
         ),
+        price: FilteredIndex::new(
+            |o| o.reserved_for.is_none() && o.price_decline.is_none(),
+            MultiIndex::new(
+                |o| (o.contract_addr.clone(), o.list_price.currency(), o.list_price.amount().u128()),
+                "offerings",
+                "offerings_price",
+            ),
+        ),
+        dutch: FilteredIndex::new(
+            |o| o.reserved_for.is_none() && o.price_decline.is_some(),
+            MultiIndex::new(
+                |o| (o.contract_addr.clone(), o.list_price.currency()),
+                "offerings",
+                "offerings_dutch",
+            ),
+        ),
+        token: UniqueIndex::new(
+            |o| (o.contract_addr.clone(), o.token_id.clone()),
+            "offerings_token",
+        ),
+        reserved: FilteredIndex::new(
+            |o| o.reserved_for.is_some(),
+            MultiIndex::new(
+                |o| o.reserved_for.clone().expect("only reserved offerings are indexed"),
+                "offerings",
+                "offerings_reserved",
+            ),
+        ),
     };
     IndexedMap::new("offerings", indexes)
 }
 
+pub fn increment_bundles(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = BUNDLES_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    BUNDLES_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+pub fn increment_rentals(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = RENTALS_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    RENTALS_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+pub fn increment_swaps(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = SWAPS_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    SWAPS_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+pub fn increment_offers(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = OFFERS_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    OFFERS_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+/**
+ * Indexes of Offer by nft and by bidder
+ */
+pub struct OfferIndexes<'a> {
+    pub token: MultiIndex<'a, (Addr, String), Offer, String>,
+    pub bidder: MultiIndex<'a, Addr, Offer, String>,
+}
+
+impl<'a> IndexList<Offer> for OfferIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Offer>> + '_> {
+        let v: Vec<&dyn Index<Offer>> = vec![&self.token, &self.bidder];
+        Box::new(v.into_iter())
+    }
+}
+
+/**
+ * offers maps the offer_id to an Offer, offer_id is derived from OFFERS_COUNT
+ */
+pub fn offers<'a>() -> IndexedMap<'a, &'a str, Offer, OfferIndexes<'a>> {
+    let indexes = OfferIndexes {
+        token: MultiIndex::new(
+            |o| (o.contract_addr.clone(), o.token_id.clone()),
+            "offers",
+            "offers_token",
+        ),
+        bidder: MultiIndex::new(
+            |o| o.bidder.clone(),
+            "offers",
+            "offers_bidder",
+        ),
+    };
+    IndexedMap::new("offers", indexes)
+}
+
+pub fn increment_collection_offers(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = COLLECTION_OFFERS_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    COLLECTION_OFFERS_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+/**
+ * CollectionOffer indexed by (collection, currency, price) to find the best bid of a collection
+ */
+pub struct CollectionOfferIndexes<'a> {
+    pub price: MultiIndex<'a, (Addr, String, u128), CollectionOffer, String>,
+}
+
+impl<'a> IndexList<CollectionOffer> for CollectionOfferIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<CollectionOffer>> + '_> {
+        let v: Vec<&dyn Index<CollectionOffer>> = vec![&self.price];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn collection_offers<'a>() -> IndexedMap<'a, &'a str, CollectionOffer, CollectionOfferIndexes<'a>> {
+    let indexes = CollectionOfferIndexes {
+        price: MultiIndex::new(
+            |o| (o.contract_addr.clone(), o.price.currency(), o.price.amount().u128()),
+            "collection_offers",
+            "collection_offers_price",
+        ),
+    };
+    IndexedMap::new("collection_offers", indexes)
+}
+
+pub fn increment_trades(storage: &mut dyn Storage) -> StdResult<u64> {
+    let val = TRADES_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    TRADES_COUNT.save(storage, &val)?;
+    Ok(val)
+}
+
+/**
+ * Trade indexed by nft contract for the history of a collection
+ */
+pub struct TradeIndexes<'a> {
+    pub contract: MultiIndex<'a, Addr, Trade, u64>,
+}
+
+impl<'a> IndexList<Trade> for TradeIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Trade>> + '_> {
+        let v: Vec<&dyn Index<Trade>> = vec![&self.contract];
+        Box::new(v.into_iter())
+    }
+}
+
+/**
+ * trades maps the trade_id to a Trade, trade_id is derived from TRADES_COUNT. It is kept as a
+ * number so that the history pages in the order of the trades
+ */
+pub fn trades<'a>() -> IndexedMap<'a, u64, Trade, TradeIndexes<'a>> {
+    let indexes = TradeIndexes {
+        contract: MultiIndex::new(
+            |t| t.contract_addr.clone(),
+            "trades",
+            "trades_contract",
+        ),
+    };
+    IndexedMap::new("trades", indexes)
+}
+
 /**
  * @author kevinnguyen <kevin.nguyen.ai@gmail.com>
  * test storage 
@@ -207,18 +724,22 @@ mod test_state {
             token_id: String::from("NFT1"),
             seller: owner1.clone(),
             contract_addr: contract1.clone(),
-            list_price: Cw20CoinVerified {
+            list_price: Price::Cw20(Cw20CoinVerified {
                 address: owner1.clone(),
                 amount: Uint128::from(1000000u128),
-            },
+            }),
+            price_decline: None,
+            expires: Expiration::Never {},
+            non_custodial: false,
+            reserved_for: None,
             extension: extension1,
         };
         // test for storage init and save
         let token_id = increment_offerings(store.borrow_mut()).unwrap();
-        offerings::<String, MockStorage >().save(store.borrow_mut(), &u64::from(token_id).to_string(), &offering_addr1).unwrap();
+        offerings::<String>().save(store.borrow_mut(), &token_id.to_string(), &offering_addr1).unwrap();
 
         // want to load Offering<T> using owner1 and contract1
-        let list: Vec<_> = offerings::<String, MemoryStorage>()
+        let list: Vec<_> = offerings::<String>()
             .idx.owner
             .prefix(owner1)
             .range(&store, None, None, Order::Ascending)
@@ -0,0 +1,359 @@
+use cosmwasm_std::{Addr, Api, Deps, DepsMut, MessageInfo, Order, Response, StdResult};
+use cw20::{Cw20CoinVerified, Cw20ReceiveMsg};
+use cw721::Cw721ReceiveMsg;
+use cw_storage_plus::Bound;
+use cw_utils::one_coin;
+
+use crate::contract::{check_cw20_sender, transfer_nft_msg, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::error::ContractError;
+use crate::msg::{CreateSwap, DepositToSwap, SwapToken};
+use crate::package::{Price, QuerySwapResult, SwapsResponse};
+use crate::state::{increment_swaps, Swap, SwapItem, MAX_SWAP_ITEMS, SWAPS};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Propose a swap, the funds attached to the message, if any, are escrowed as a native sweetener
+ */
+pub fn try_create_swap(
+    deps: DepsMut,
+    info: MessageInfo,
+    msg: CreateSwap,
+) -> Result<Response, ContractError> {
+    let sweetener = match info.funds.is_empty() {
+        true => None,
+        false => Some(Price::Native(one_coin(&info)?)),
+    };
+    save_swap(deps, info.sender, sweetener, msg)
+}
+
+/**
+ * Propose a swap with the CW20 tokens sent through the `Send` hook as its sweetener
+ */
+pub fn try_receive_create_swap(
+    deps: DepsMut,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: CreateSwap,
+) -> Result<Response, ContractError> {
+    check_cw20_sender(deps.as_ref(), &info.sender)?;
+    let maker = deps.api.addr_validate(&rcv_msg.sender)?;
+    let sweetener = Price::Cw20(Cw20CoinVerified {
+        address: info.sender,
+        amount: rcv_msg.amount,
+    });
+    save_swap(deps, maker, Some(sweetener), msg)
+}
+
+fn save_swap(
+    deps: DepsMut,
+    maker: Addr,
+    sweetener: Option<Price>,
+    msg: CreateSwap,
+) -> Result<Response, ContractError> {
+    let counterparty = deps.api.addr_validate(&msg.counterparty)?;
+    if counterparty == maker {
+        return Err(ContractError::SelfSwap {});
+    }
+    let offered = swap_items(deps.api, &[], msg.offered)?;
+    let wanted = swap_items(deps.api, &offered, msg.wanted)?;
+
+    let id = increment_swaps(deps.storage)?.to_string();
+    let swap = Swap {
+        maker,
+        counterparty,
+        offered,
+        wanted,
+        sweetener,
+    };
+    SWAPS.save(deps.storage, &id, &swap)?;
+
+    let res = Response::new()
+        .add_attribute("action", "create_swap")
+        .add_attribute("swap_id", id)
+        .add_attribute("maker", swap.maker)
+        .add_attribute("counterparty", swap.counterparty);
+    Ok(match swap.sweetener {
+        Some(sweetener) => res.add_attribute("sweetener", sweetener.to_string()),
+        None => res,
+    })
+}
+
+// one side of a swap, between 1 and MAX_SWAP_ITEMS nfts found neither twice nor on the `other` side
+fn swap_items(api: &dyn Api, other: &[SwapItem], tokens: Vec<SwapToken>) -> Result<Vec<SwapItem>, ContractError> {
+    if tokens.is_empty() || tokens.len() > MAX_SWAP_ITEMS {
+        return Err(ContractError::InvalidSwap { max: MAX_SWAP_ITEMS });
+    }
+    let mut items: Vec<SwapItem> = vec![];
+    for token in tokens {
+        let contract_addr = api.addr_validate(&token.contract_addr)?;
+        if items.iter().chain(other).any(|i| i.contract_addr == contract_addr && i.token_id == token.token_id) {
+            return Err(ContractError::DuplicateSwapItem { contract_addr: contract_addr.into(), token_id: token.token_id });
+        }
+        items.push(SwapItem {
+            contract_addr,
+            token_id: token.token_id,
+            deposited: false,
+        });
+    }
+    Ok(items)
+}
+
+/**
+ * Deposit the nft sent with `SendNft`, `info.sender` is the nft contract. The maker deposits its
+ * offered nfts and the counterparty the wanted ones. The last deposit releases both sides: the
+ * offered nfts and the sweetener go to the counterparty, the wanted nfts to the maker
+ */
+pub fn try_deposit_to_swap(
+    deps: DepsMut,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+    msg: DepositToSwap,
+) -> Result<Response, ContractError> {
+    let mut swap = SWAPS.load(deps.storage, &msg.swap_id)?;
+    let sender = deps.api.addr_validate(&rcv_msg.sender)?;
+    let side = if sender == swap.maker {
+        &mut swap.offered
+    } else if sender == swap.counterparty {
+        &mut swap.wanted
+    } else {
+        return Err(ContractError::Unauthorized {});
+    };
+    let item = side
+        .iter_mut()
+        .find(|i| i.contract_addr == info.sender && i.token_id == rcv_msg.token_id && !i.deposited)
+        .ok_or(ContractError::NotInSwap {})?;
+    item.deposited = true;
+
+    let res = Response::new()
+        .add_attribute("action", "deposit_to_swap")
+        .add_attribute("swap_id", msg.swap_id.clone())
+        .add_attribute("depositor", sender)
+        .add_attribute("contract_addr", info.sender)
+        .add_attribute("token_id", rcv_msg.token_id);
+    if !swap.complete() {
+        SWAPS.save(deps.storage, &msg.swap_id, &swap)?;
+        return Ok(res);
+    }
+
+    SWAPS.remove(deps.storage, &msg.swap_id);
+    let mut messages = vec![];
+    for item in &swap.offered {
+        messages.push(transfer_nft_msg(&item.contract_addr, &swap.counterparty, &item.token_id)?);
+    }
+    for item in &swap.wanted {
+        messages.push(transfer_nft_msg(&item.contract_addr, &swap.maker, &item.token_id)?);
+    }
+    if let Some(sweetener) = &swap.sweetener {
+        messages.push(sweetener.transfer_msg(&swap.counterparty, sweetener.amount())?);
+    }
+    Ok(res.add_attribute("result", "swapped").add_messages(messages))
+}
+
+/**
+ * Cancel a swap before it completes, by either party. Every deposited nft goes back to its
+ * depositor and the sweetener to the maker
+ */
+pub fn try_cancel_swap(
+    deps: DepsMut,
+    info: MessageInfo,
+    swap_id: String,
+) -> Result<Response, ContractError> {
+    let swap = SWAPS.load(deps.storage, &swap_id)?;
+    if info.sender != swap.maker && info.sender != swap.counterparty {
+        return Err(ContractError::Unauthorized {});
+    }
+    SWAPS.remove(deps.storage, &swap_id);
+
+    let mut messages = vec![];
+    for item in swap.offered.iter().filter(|i| i.deposited) {
+        messages.push(transfer_nft_msg(&item.contract_addr, &swap.maker, &item.token_id)?);
+    }
+    for item in swap.wanted.iter().filter(|i| i.deposited) {
+        messages.push(transfer_nft_msg(&item.contract_addr, &swap.counterparty, &item.token_id)?);
+    }
+    if let Some(sweetener) = &swap.sweetener {
+        messages.push(sweetener.transfer_msg(&swap.maker, sweetener.amount())?);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_swap")
+        .add_attribute("swap_id", swap_id)
+        .add_attribute("cancelled_by", info.sender)
+        .add_messages(messages)
+    )
+}
+
+// ================================ Query Handlers ==================================================
+
+pub fn query_swap(deps: Deps, swap_id: String) -> StdResult<QuerySwapResult> {
+    let swap = SWAPS.load(deps.storage, &swap_id)?;
+    Ok(parse_swap(swap_id, swap))
+}
+
+pub fn query_swaps(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<SwapsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let swaps = SWAPS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, swap)| parse_swap(id, swap)))
+        .collect::<StdResult<_>>()?;
+    Ok(SwapsResponse { swaps })
+}
+
+fn parse_swap(id: String, swap: Swap) -> QuerySwapResult {
+    QuerySwapResult {
+        id,
+        maker: swap.maker,
+        counterparty: swap.counterparty,
+        offered: swap.offered,
+        wanted: swap.wanted,
+        sweetener: swap.sweetener,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{init_market, mock_cw20};
+    use crate::contract::{execute, query};
+    use crate::msg::{HandleMsg, QueryMsg, ReceiveMsg, ReceiveNftMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, from_binary, to_binary, BankMsg, CosmosMsg, StdError, Uint128};
+
+    fn token(contract_addr: &str, token_id: &str) -> SwapToken {
+        SwapToken { contract_addr: contract_addr.to_string(), token_id: token_id.to_string() }
+    }
+
+    fn create_swap() -> CreateSwap {
+        CreateSwap {
+            counterparty: String::from("bob"),
+            offered: vec![token("apes", "Ape1"), token("apes", "Ape2")],
+            wanted: vec![token("punks", "Punk1")],
+        }
+    }
+
+    fn deposit(deps: DepsMut, sender: &str, contract_addr: &str, token_id: &str) -> Result<Response, ContractError> {
+        let msg = HandleMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_binary(&ReceiveNftMsg::DepositToSwap(DepositToSwap { swap_id: String::from("1") })).unwrap(),
+        });
+        execute(deps, mock_env(), mock_info(contract_addr, &[]), msg)
+    }
+
+    #[test]
+    fn swap_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let msg = HandleMsg::CreateSwap(create_swap());
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &coins(50, "ujuno")), msg).unwrap();
+
+        // each party can only deposit its own side
+        let err = deposit(deps.as_mut(), "alice", "punks", "Punk1").unwrap_err();
+        assert!(matches!(err, ContractError::NotInSwap {}));
+        let err = deposit(deps.as_mut(), "eve", "apes", "Ape1").unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        deposit(deps.as_mut(), "bob", "punks", "Punk1").unwrap();
+        deposit(deps.as_mut(), "alice", "apes", "Ape1").unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Swap { swap_id: String::from("1") }).unwrap();
+        let swap: QuerySwapResult = from_binary(&res).unwrap();
+        assert!(swap.wanted[0].deposited && swap.offered[0].deposited && !swap.offered[1].deposited);
+
+        // the last deposit releases both sides
+        let res = deposit(deps.as_mut(), "alice", "apes", "Ape2").unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                transfer_nft_msg(&Addr::unchecked("apes"), &Addr::unchecked("bob"), "Ape1").unwrap(),
+                transfer_nft_msg(&Addr::unchecked("apes"), &Addr::unchecked("bob"), "Ape2").unwrap(),
+                transfer_nft_msg(&Addr::unchecked("punks"), &Addr::unchecked("alice"), "Punk1").unwrap(),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "bob".to_string(), amount: coins(50, "ujuno") }),
+            ]
+        );
+        assert!(SWAPS.may_load(&deps.storage, "1").unwrap().is_none());
+    }
+
+    #[test]
+    fn cancelled_swap_returns_deposits() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        mock_cw20(&mut deps);
+        let msg = HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("alice"),
+            amount: Uint128::new(7),
+            msg: to_binary(&ReceiveMsg::CreateSwap(create_swap())).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), msg).unwrap();
+        deposit(deps.as_mut(), "alice", "apes", "Ape2").unwrap();
+        deposit(deps.as_mut(), "bob", "punks", "Punk1").unwrap();
+
+        let cancel = HandleMsg::CancelSwap { swap_id: String::from("1") };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("eve", &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), cancel).unwrap();
+        let sweetener = Price::Cw20(Cw20CoinVerified { address: Addr::unchecked("cw20contract"), amount: Uint128::new(7) });
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                transfer_nft_msg(&Addr::unchecked("apes"), &Addr::unchecked("alice"), "Ape2").unwrap(),
+                transfer_nft_msg(&Addr::unchecked("punks"), &Addr::unchecked("bob"), "Punk1").unwrap(),
+                sweetener.transfer_msg(&Addr::unchecked("alice"), Uint128::new(7)).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_and_late_deposits_rejected() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), HandleMsg::CreateSwap(create_swap())).unwrap();
+
+        // an nft already held for the swap can't be deposited again
+        deposit(deps.as_mut(), "alice", "apes", "Ape1").unwrap();
+        let err = deposit(deps.as_mut(), "alice", "apes", "Ape1").unwrap_err();
+        assert!(matches!(err, ContractError::NotInSwap {}));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Swap { swap_id: String::from("1") }).unwrap();
+        let swap: QuerySwapResult = from_binary(&res).unwrap();
+        assert!(swap.offered[0].deposited && !swap.offered[1].deposited);
+
+        // nor can anything be deposited once the swap is cancelled
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), HandleMsg::CancelSwap { swap_id: String::from("1") }).unwrap();
+        let err = deposit(deps.as_mut(), "bob", "punks", "Punk1").unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+    }
+
+    #[test]
+    fn self_swap_rejected() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        let msg = CreateSwap { counterparty: String::from("alice"), ..create_swap() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), HandleMsg::CreateSwap(msg)).unwrap_err();
+        assert!(matches!(err, ContractError::SelfSwap {}));
+    }
+
+    #[test]
+    fn duplicate_swap_items_rejected() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+
+        // twice on the same side
+        let msg = CreateSwap { offered: vec![token("apes", "Ape1"), token("apes", "Ape1")], ..create_swap() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), HandleMsg::CreateSwap(msg)).unwrap_err();
+        assert!(matches!(err, ContractError::DuplicateSwapItem { contract_addr, token_id } if contract_addr == "apes" && token_id == "Ape1"));
+
+        // or both offered and wanted
+        let msg = CreateSwap { wanted: vec![token("punks", "Punk1"), token("apes", "Ape2")], ..create_swap() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), HandleMsg::CreateSwap(msg)).unwrap_err();
+        assert!(matches!(err, ContractError::DuplicateSwapItem { contract_addr, token_id } if contract_addr == "apes" && token_id == "Ape2"));
+
+        // the same token id of another collection is a different nft
+        let msg = CreateSwap { wanted: vec![token("punks", "Ape1")], ..create_swap() };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), HandleMsg::CreateSwap(msg)).unwrap();
+    }
+}
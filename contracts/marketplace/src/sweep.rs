@@ -0,0 +1,201 @@
+use cosmwasm_std::{Addr, DepsMut, Env, MessageInfo, Response, Uint128};
+use cw20::{Cw20CoinVerified, Cw20ReceiveMsg};
+use cw_utils::one_coin;
+use std::mem::discriminant;
+
+use crate::contract::{sale_payout, transfer_nft_msg};
+use crate::error::ContractError;
+use crate::history::record_sale;
+use crate::listing::listing_approved;
+use crate::msg::BuyMany;
+use crate::package::Price;
+use crate::state::{offerings, MAX_SWEEP_OFFERINGS};
+
+// =================================== Message Handlers ========================================
+
+/**
+ * Sweep Offerings listed in a native coin, paying with the single coin attached to the message
+ */
+pub fn try_buy_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: BuyMany,
+) -> Result<Response, ContractError> {
+    let payment = Price::Native(one_coin(&info)?);
+    settle_sweep(deps, env, msg, info.sender, payment)
+}
+
+/**
+ * Sweep Offerings listed in a CW20 token with the tokens sent through the `Send` hook,
+ * `info.sender` is the token contract
+ */
+pub fn try_receive_buy_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw20ReceiveMsg,
+    msg: BuyMany,
+) -> Result<Response, ContractError> {
+    let payment = Price::Cw20(Cw20CoinVerified {
+        address: info.sender,
+        amount: rcv_msg.amount,
+    });
+    let buyer = deps.api.addr_validate(&rcv_msg.sender)?;
+    settle_sweep(deps, env, msg, buyer, payment)
+}
+
+/**
+ * Buy every Offering of the sweep at its current price, or none of them: the whole execution
+ * fails if one is gone, expired, stale or priced in another currency than the payment, or if
+ * the total goes above `max_total`. What is paid above the total is refunded to the buyer
+ */
+fn settle_sweep(
+    deps: DepsMut,
+    env: Env,
+    msg: BuyMany,
+    buyer: Addr,
+    payment: Price,
+) -> Result<Response, ContractError> {
+    let distinct = msg.offering_ids.iter().enumerate().all(|(i, id)| !msg.offering_ids[..i].contains(id));
+    if msg.offering_ids.is_empty() || msg.offering_ids.len() > MAX_SWEEP_OFFERINGS || !distinct {
+        return Err(ContractError::InvalidSweep { max: MAX_SWEEP_OFFERINGS });
+    }
+
+    // check every Offering and the total before anything is bought
+    let mut sales = vec![];
+    let mut total = Uint128::zero();
+    for offering_id in &msg.offering_ids {
+        let off = offerings::<String>().load(deps.storage, offering_id)?;
+        if off.expires.is_expired(&env.block) {
+            return Err(ContractError::OfferingExpired {});
+        }
+        if off.reserved_for.as_ref().map_or(false, |reserved_for| *reserved_for != buyer) {
+            return Err(ContractError::ReservedForAnotherBuyer {});
+        }
+        if discriminant(&off.list_price) != discriminant(&payment) || off.list_price.currency() != payment.currency() {
+            return Err(ContractError::CurrencyMismatch {});
+        }
+        if off.non_custodial && !listing_approved(deps.as_ref(), &env, &off.contract_addr, &off.token_id, &off.seller)? {
+            return Err(ContractError::StaleListing {});
+        }
+        let price = off.current_price(env.block.time);
+        total += price;
+        sales.push((offering_id, off, price));
+    }
+    if total > msg.max_total {
+        return Err(ContractError::MaxTotalExceeded { total, max_total: msg.max_total });
+    }
+    let paid = payment.amount();
+    if paid < total {
+        return Err(ContractError::InsufficientFunds {});
+    }
+
+    let mut payouts = vec![];
+    let mut transfers = vec![];
+    let (mut fee, mut royalty) = (Uint128::zero(), Uint128::zero());
+    for (offering_id, off, price) in sales {
+        let payout = sale_payout(deps.as_ref(), &off.list_price, price, &off.contract_addr, &off.token_id, &off.seller)?;
+        fee += payout.fee;
+        royalty += payout.royalty;
+        payouts.extend(payout.messages);
+        transfers.push(transfer_nft_msg(&off.contract_addr, &buyer, &off.token_id)?);
+        offerings::<String>().remove(deps.storage, offering_id)?;
+        let paid_price = off.list_price.with_amount(price);
+        record_sale(deps.storage, &env, &off.contract_addr, &off.token_id, &off.seller, &buyer, paid_price)?;
+    }
+    if paid > total {
+        payouts.push(payment.transfer_msg(&buyer, paid - total)?);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "buy_many")
+        .add_attribute("buyer", buyer)
+        .add_attribute("offering_ids", msg.offering_ids.join(","))
+        .add_attribute("paid_price", payment.with_amount(total).to_string())
+        .add_attribute("fee", fee)
+        .add_attribute("royalty", royalty)
+        .add_messages(payouts)
+        .add_messages(transfers)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::tests::{cw20_price, init_market, sell_nft};
+    use crate::contract::execute;
+    use crate::msg::{HandleMsg, ReceiveMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, to_binary, BankMsg, CosmosMsg, StdError};
+
+    fn sweep(offering_ids: &[&str], max_total: u128) -> BuyMany {
+        BuyMany {
+            offering_ids: offering_ids.iter().map(|id| id.to_string()).collect(),
+            max_total: Uint128::new(max_total),
+        }
+    }
+
+    #[test]
+    fn native_sweep_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "Floor1", Price::Native(coin(100, "ujuno")));
+        sell_nft(deps.as_mut(), "Floor2", Price::Native(coin(120, "ujuno")));
+        sell_nft(deps.as_mut(), "Other", cw20_price(100));
+
+        let buy = |ids: &[&str], max_total| HandleMsg::BuyMany(sweep(ids, max_total));
+        let funds = coins(250, "ujuno");
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), buy(&[], 250)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSweep { .. }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), buy(&["1", "2"], 200)).unwrap_err();
+        assert!(matches!(err, ContractError::MaxTotalExceeded { .. }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), buy(&["1", "3"], 250)).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyMismatch {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), buy(&["1", "1"], 250)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSweep { .. }));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), buy(&["1", "9"], 250)).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("buyer", &coins(200, "ujuno")), buy(&["1", "2"], 250)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+
+        // both nfts are bought and the extra funds refunded
+        let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &funds), buy(&["1", "2"], 250)).unwrap();
+        let msgs: Vec<_> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(100, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "seller".to_string(), amount: coins(120, "ujuno") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "buyer".to_string(), amount: coins(30, "ujuno") }),
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Floor1").unwrap(),
+                transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Floor2").unwrap(),
+            ]
+        );
+        assert!(offerings::<String>().may_load(&deps.storage, "1").unwrap().is_none());
+        assert!(offerings::<String>().may_load(&deps.storage, "2").unwrap().is_none());
+    }
+
+    #[test]
+    fn cw20_sweep_path() {
+        let mut deps = mock_dependencies();
+        init_market(deps.as_mut(), 0);
+        sell_nft(deps.as_mut(), "Floor1", cw20_price(10));
+        sell_nft(deps.as_mut(), "Floor2", cw20_price(15));
+
+        let receive = |amount: u128| HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: String::from("buyer"),
+            amount: Uint128::new(amount),
+            msg: to_binary(&ReceiveMsg::BuyMany(sweep(&["1", "2"], 25))).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("othercw20", &[]), receive(25)).unwrap_err();
+        assert!(matches!(err, ContractError::CurrencyMismatch {}));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("cw20contract", &[]), receive(25)).unwrap();
+        assert_eq!(res.messages.len(), 4);
+        assert_eq!(
+            res.messages[3].msg,
+            transfer_nft_msg(&Addr::unchecked("nftcontract"), &Addr::unchecked("buyer"), "Floor2").unwrap()
+        );
+    }
+}
@@ -7,7 +7,10 @@ use cw721::{
     AllNftInfoResponse, ApprovalResponse, ApprovalsResponse, ContractInfoResponse, NftInfoResponse,
     NumTokensResponse, OperatorsResponse, OwnerOfResponse, TokensResponse,
 };
-use rvn_base::{ExecuteMsg, Extension, InstantiateMsg, MinterResponse, QueryMsg};
+use rvn_base::{
+    ExecuteMsg, Extension, InstantiateMsg, MinterResponse, QueryMsg, RoyaltiesInfoResponse,
+    UserOfResponse,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -28,6 +31,7 @@ fn main() {
     export_schema(&schema_for!(OperatorsResponse), &out_dir);
     export_schema(&schema_for!(ContractInfoResponse), &out_dir);
     export_schema(&schema_for!(MinterResponse), &out_dir);
+    export_schema(&schema_for!(RoyaltiesInfoResponse), &out_dir);
     export_schema_with_title(
         &schema_for!(NftInfoResponse<Extension>),
         &out_dir,
@@ -36,4 +40,5 @@ fn main() {
     export_schema(&schema_for!(NumTokensResponse), &out_dir);
     export_schema(&schema_for!(OwnerOfResponse), &out_dir);
     export_schema(&schema_for!(TokensResponse), &out_dir);
+    export_schema(&schema_for!(UserOfResponse), &out_dir);
 }
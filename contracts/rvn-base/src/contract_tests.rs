@@ -1,6 +1,6 @@
 #![cfg(test)]
 use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{from_binary, to_binary, CosmosMsg, DepsMut, Empty, Response, WasmMsg};
+use cosmwasm_std::{from_binary, to_binary, CosmosMsg, DepsMut, Empty, Response, Uint128, WasmMsg};
 
 use cw721::{
     Approval, ApprovalResponse, ContractInfoResponse, Cw721Query, Cw721ReceiveMsg, Expiration,
@@ -9,6 +9,7 @@ use cw721::{
 
 use crate::{
     ContractError, Cw721Contract, ExecuteMsg, Extension, InstantiateMsg, MintMsg, QueryMsg,
+    RoyaltiesInfoResponse, RoyaltyMsg, UserOfResponse,
 };
 
 const MINTER: &str = "merlin";
@@ -21,6 +22,7 @@ fn setup_contract(deps: DepsMut<'_>) -> Cw721Contract<'static, Extension, Empty>
         name: CONTRACT_NAME.to_string(),
         symbol: SYMBOL.to_string(),
         minter: String::from(MINTER),
+        royalty: None,
     };
     let info = mock_info("creator", &[]);
     let res = contract.instantiate(deps, mock_env(), info, msg).unwrap();
@@ -37,6 +39,7 @@ fn proper_instantiation() {
         name: CONTRACT_NAME.to_string(),
         symbol: SYMBOL.to_string(),
         minter: String::from(MINTER),
+        royalty: None,
     };
     let info = mock_info("creator", &[]);
 
@@ -749,3 +752,231 @@ fn query_tokens_by_owner() {
         .unwrap();
     assert_eq!(&by_demeter[1..], &tokens.tokens[..]);
 }
+
+#[test]
+fn royalties() {
+    let mut deps = mock_dependencies();
+    let contract = setup_contract(deps.as_mut());
+    let minter = mock_info(MINTER, &[]);
+
+    let token_id = "grow".to_string();
+    let mint_msg = ExecuteMsg::Mint(MintMsg::<Extension> {
+        token_id: token_id.clone(),
+        owner: String::from("demeter"),
+        token_uri: None,
+        extension: None,
+    });
+    contract
+        .execute(deps.as_mut(), mock_env(), minter.clone(), mint_msg)
+        .unwrap();
+
+    // no royalty by default
+    let res = contract
+        .royalty_info(deps.as_ref(), token_id.clone(), Uint128::new(1000))
+        .unwrap();
+    assert_eq!(
+        res,
+        RoyaltiesInfoResponse {
+            address: String::new(),
+            royalty_amount: Uint128::zero(),
+        }
+    );
+
+    // only the minter can set royalties, up to 100 percent
+    let set_collection = ExecuteMsg::SetRoyalty {
+        token_id: None,
+        royalty: Some(RoyaltyMsg {
+            payment_address: String::from("creator"),
+            percentage: 5,
+        }),
+    };
+    let err = contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("random", &[]),
+            set_collection.clone(),
+        )
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let invalid = ExecuteMsg::SetRoyalty {
+        token_id: None,
+        royalty: Some(RoyaltyMsg {
+            payment_address: String::from("creator"),
+            percentage: 101,
+        }),
+    };
+    let err = contract
+        .execute(deps.as_mut(), mock_env(), minter.clone(), invalid)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidRoyaltyPercentage {});
+
+    // collection royalty applies to every token
+    contract
+        .execute(deps.as_mut(), mock_env(), minter.clone(), set_collection)
+        .unwrap();
+    let query_msg = QueryMsg::RoyaltyInfo {
+        token_id: token_id.clone(),
+        sale_price: Uint128::new(1000),
+    };
+    let res: RoyaltiesInfoResponse = from_binary(
+        &contract
+            .query(deps.as_ref(), mock_env(), query_msg.clone())
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        RoyaltiesInfoResponse {
+            address: String::from("creator"),
+            royalty_amount: Uint128::new(50),
+        }
+    );
+
+    // token royalty overrides the collection one until it is cleared
+    let set_token = ExecuteMsg::SetRoyalty {
+        token_id: Some(token_id.clone()),
+        royalty: Some(RoyaltyMsg {
+            payment_address: String::from("artist"),
+            percentage: 10,
+        }),
+    };
+    contract
+        .execute(deps.as_mut(), mock_env(), minter.clone(), set_token)
+        .unwrap();
+    let res: RoyaltiesInfoResponse = from_binary(
+        &contract
+            .query(deps.as_ref(), mock_env(), query_msg.clone())
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.address, "artist");
+    assert_eq!(res.royalty_amount, Uint128::new(100));
+
+    let clear_token = ExecuteMsg::SetRoyalty {
+        token_id: Some(token_id),
+        royalty: None,
+    };
+    contract
+        .execute(deps.as_mut(), mock_env(), minter, clear_token)
+        .unwrap();
+    let res: RoyaltiesInfoResponse = from_binary(
+        &contract
+            .query(deps.as_ref(), mock_env(), query_msg)
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.address, "creator");
+}
+
+#[test]
+fn user_role() {
+    let mut deps = mock_dependencies();
+    let contract = setup_contract(deps.as_mut());
+
+    let token_id = "rental".to_string();
+    let mint_msg = ExecuteMsg::Mint(MintMsg::<Extension> {
+        token_id: token_id.clone(),
+        owner: String::from("landlord"),
+        token_uri: None,
+        extension: None,
+    });
+    contract
+        .execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), mint_msg)
+        .unwrap();
+
+    // only someone allowed to transfer the token can set its user
+    let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(100));
+    let set_user = |user: &str| ExecuteMsg::SetUser {
+        token_id: token_id.clone(),
+        user: String::from(user),
+        expires,
+    };
+    let err = contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("tenant", &[]),
+            set_user("tenant"),
+        )
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("landlord", &[]),
+            set_user("tenant"),
+        )
+        .unwrap();
+
+    // the owner can neither replace the user, burn the token, nor drop the user by transferring it
+    let err = contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("landlord", &[]),
+            set_user("other"),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UserNotExpired {
+            expires: expires.to_string()
+        }
+    );
+    let burn = ExecuteMsg::Burn {
+        token_id: token_id.clone(),
+    };
+    let err = contract
+        .execute(deps.as_mut(), mock_env(), mock_info("landlord", &[]), burn)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::UserNotExpired { .. }));
+    let transfer = ExecuteMsg::TransferNft {
+        recipient: String::from("buyer"),
+        token_id: token_id.clone(),
+    };
+    contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("landlord", &[]),
+            transfer,
+        )
+        .unwrap();
+    let query_msg = QueryMsg::UserOf {
+        token_id: token_id.clone(),
+    };
+    let res: UserOfResponse = from_binary(
+        &contract
+            .query(deps.as_ref(), mock_env(), query_msg.clone())
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        UserOfResponse {
+            user: Some(String::from("tenant")),
+            expires: Some(expires),
+        }
+    );
+
+    // once expired, the user is gone and the new owner can set another one
+    let mut later = mock_env();
+    later.block.time = later.block.time.plus_seconds(100);
+    let res: UserOfResponse = from_binary(
+        &contract
+            .query(deps.as_ref(), later.clone(), query_msg)
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.user, None);
+    let set_user = ExecuteMsg::SetUser {
+        token_id,
+        user: String::from("other"),
+        expires: Expiration::AtTime(later.block.time.plus_seconds(100)),
+    };
+    contract
+        .execute(deps.as_mut(), later, mock_info("buyer", &[]), set_user)
+        .unwrap();
+}
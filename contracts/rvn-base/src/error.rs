@@ -17,4 +17,10 @@ pub enum ContractError {
 
     #[error("Approval not found for: {spender}")]
     ApprovalNotFound { spender: String },
+
+    #[error("Token is used until {expires}")]
+    UserNotExpired { expires: String },
+
+    #[error("Royalty percentage must be between 0 and 100")]
+    InvalidRoyaltyPercentage {},
 }
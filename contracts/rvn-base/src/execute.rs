@@ -1,14 +1,14 @@
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response};
 
 use cw2::set_contract_version;
 use cw721::{ContractInfoResponse, CustomMsg, Cw721Execute, Cw721ReceiveMsg, Expiration};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, MintMsg};
-use crate::state::{Approval, Cw721Contract, TokenInfo};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MintMsg, RoyaltyMsg};
+use crate::state::{Approval, Cw721Contract, Royalty, TokenInfo, UserInfo};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw721-base";
@@ -25,7 +25,7 @@ where
         _env: Env,
         _info: MessageInfo,
         msg: InstantiateMsg,
-    ) -> StdResult<Response<C>> {
+    ) -> Result<Response<C>, ContractError> {
         set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
         let info = ContractInfoResponse {
@@ -35,6 +35,10 @@ where
         self.contract_info.save(deps.storage, &info)?;
         let minter = deps.api.addr_validate(&msg.minter)?;
         self.minter.save(deps.storage, &minter)?;
+        if let Some(royalty) = msg.royalty {
+            let royalty = validate_royalty(deps.as_ref(), royalty)?;
+            self.royalty.save(deps.storage, &royalty)?;
+        }
         Ok(Response::default())
     }
 
@@ -69,6 +73,14 @@ where
                 msg,
             } => self.send_nft(deps, env, info, contract, token_id, msg),
             ExecuteMsg::Burn { token_id } => self.burn(deps, env, info, token_id),
+            ExecuteMsg::SetUser {
+                token_id,
+                user,
+                expires,
+            } => self.set_user(deps, env, info, token_id, user, expires),
+            ExecuteMsg::SetRoyalty { token_id, royalty } => {
+                self.set_royalty(deps, env, info, token_id, royalty)
+            }
         }
     }
 }
@@ -98,6 +110,7 @@ where
             approvals: vec![],
             token_uri: msg.token_uri,
             extension: msg.extension,
+            user: None,
         };
         self.tokens
             .update(deps.storage, &msg.token_id, |old| match old {
@@ -113,6 +126,92 @@ where
             .add_attribute("owner", msg.owner)
             .add_attribute("token_id", msg.token_id))
     }
+
+    pub fn set_user(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        user: String,
+        expires: Expiration,
+    ) -> Result<Response<C>, ContractError> {
+        let mut token = self.tokens.load(deps.storage, &token_id)?;
+        self.check_can_send(deps.as_ref(), &env, &info, &token)?;
+
+        // the owner can't take the token back from its user before the expiration
+        if let Some(current) = token.user.as_ref().filter(|u| !u.is_expired(&env.block)) {
+            return Err(ContractError::UserNotExpired {
+                expires: current.expires.to_string(),
+            });
+        }
+        if expires.is_expired(&env.block) {
+            return Err(ContractError::Expired {});
+        }
+        token.user = Some(UserInfo {
+            user: deps.api.addr_validate(&user)?,
+            expires,
+        });
+        self.tokens.save(deps.storage, &token_id, &token)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_user")
+            .add_attribute("sender", info.sender)
+            .add_attribute("user", user)
+            .add_attribute("expires", expires.to_string())
+            .add_attribute("token_id", token_id))
+    }
+
+    pub fn set_royalty(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        token_id: Option<String>,
+        royalty: Option<RoyaltyMsg>,
+    ) -> Result<Response<C>, ContractError> {
+        let minter = self.minter.load(deps.storage)?;
+
+        if info.sender != minter {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let royalty = royalty
+            .map(|r| validate_royalty(deps.as_ref(), r))
+            .transpose()?;
+        match (&token_id, &royalty) {
+            (Some(token_id), Some(royalty)) => {
+                // only existing tokens can carry a royalty
+                self.tokens.load(deps.storage, token_id)?;
+                self.token_royalties.save(deps.storage, token_id, royalty)?
+            }
+            (Some(token_id), None) => self.token_royalties.remove(deps.storage, token_id),
+            (None, Some(royalty)) => self.royalty.save(deps.storage, royalty)?,
+            (None, None) => self.royalty.remove(deps.storage),
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "set_royalty")
+            .add_attribute("minter", info.sender)
+            .add_attribute("token_id", token_id.unwrap_or_default())
+            .add_attribute(
+                "percentage",
+                royalty
+                    .map(|r| r.percentage)
+                    .unwrap_or_default()
+                    .to_string(),
+            ))
+    }
+}
+
+fn validate_royalty(deps: Deps, royalty: RoyaltyMsg) -> Result<Royalty, ContractError> {
+    if royalty.percentage > 100 {
+        return Err(ContractError::InvalidRoyaltyPercentage {});
+    }
+    Ok(Royalty {
+        payment_address: deps.api.addr_validate(&royalty.payment_address)?,
+        percentage: royalty.percentage,
+    })
 }
 
 impl<'a, T, C> Cw721Execute<T, C> for Cw721Contract<'a, T, C>
@@ -252,8 +351,15 @@ where
     ) -> Result<Response<C>, ContractError> {
         let token = self.tokens.load(deps.storage, &token_id)?;
         self.check_can_send(deps.as_ref(), &env, &info, &token)?;
+        // burning would revoke the user before its expiration
+        if let Some(user) = token.user.as_ref().filter(|u| !u.is_expired(&env.block)) {
+            return Err(ContractError::UserNotExpired {
+                expires: user.expires.to_string(),
+            });
+        }
 
         self.tokens.remove(deps.storage, &token_id)?;
+        self.token_royalties.remove(deps.storage, &token_id);
         self.decrement_tokens(deps.storage)?;
 
         Ok(Response::new()
@@ -305,11 +411,7 @@ where
 
         // update the approval list (remove any for the same spender before adding)
         let spender_addr = deps.api.addr_validate(spender)?;
-        token.approvals = token
-            .approvals
-            .into_iter()
-            .filter(|apr| apr.spender != spender_addr)
-            .collect();
+        token.approvals.retain(|apr| apr.spender != spender_addr);
 
         // only difference between approve and revoke
         if add {
@@ -7,7 +7,10 @@ mod query;
 pub mod state;
 
 pub use crate::error::ContractError;
-pub use crate::msg::{ExecuteMsg, InstantiateMsg, MintMsg, MinterResponse, QueryMsg};
+pub use crate::msg::{
+    ExecuteMsg, InstantiateMsg, MintMsg, MinterResponse, QueryMsg, RoyaltiesInfoResponse,
+    RoyaltyMsg, UserOfResponse,
+};
 pub use crate::state::Cw721Contract;
 use cosmwasm_std::Empty;
 
@@ -28,7 +31,7 @@ pub mod entry {
         env: Env,
         info: MessageInfo,
         msg: InstantiateMsg,
-    ) -> StdResult<Response> {
+    ) -> Result<Response, ContractError> {
         let tract = Cw721Contract::<Extension, Empty>::default();
         tract.instantiate(deps, env, info, msg)
     }
@@ -1,7 +1,7 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Binary;
+use cosmwasm_std::{Binary, Uint128};
 use cw721::Expiration;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -15,6 +15,18 @@ pub struct InstantiateMsg {
     /// This is designed for a base NFT that is controlled by an external program
     /// or contract. You will likely replace this with custom logic in custom NFTs
     pub minter: String,
+
+    /// Royalty paid on secondary sales of every token of the collection
+    pub royalty: Option<RoyaltyMsg>,
+}
+
+/// Royalty paid to the creator on secondary sales, in the style of CW-2981
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoyaltyMsg {
+    /// Address receiving the royalties
+    pub payment_address: String,
+    /// Percentage of the sale price paid to `payment_address`, between 0 and 100
+    pub percentage: u64,
 }
 
 /// This is like Cw721ExecuteMsg but we add a Mint command for an owner
@@ -55,6 +67,21 @@ pub enum ExecuteMsg<T> {
 
     /// Burn an NFT the sender has access to
     Burn { token_id: String },
+
+    /// Set the user of a token until `expires`, in the style of ERC-4907. Can be called by
+    /// anyone allowed to transfer the token, once the previous user has expired
+    SetUser {
+        token_id: String,
+        user: String,
+        expires: Expiration,
+    },
+
+    /// Set or clear the royalty of the collection, or of a single token if `token_id`
+    /// is set. Can only be called by the contract minter
+    SetRoyalty {
+        token_id: Option<String>,
+        royalty: Option<RoyaltyMsg>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -142,6 +169,22 @@ pub enum QueryMsg {
 
     // Return the minter
     Minter {},
+
+    /// With ERC-4907 style rental extension.
+    /// Returns the current user of the token, if it has one that has not expired.
+    /// Return type: `UserOfResponse`
+    UserOf {
+        token_id: String,
+    },
+
+    /// With CW-2981 royalties extension.
+    /// Returns the royalty owed for a sale of `token_id` at `sale_price`, the token
+    /// royalty takes precedence over the collection one.
+    /// Return type: `RoyaltiesInfoResponse`
+    RoyaltyInfo {
+        token_id: String,
+        sale_price: Uint128,
+    },
 }
 
 /// Shows who can mint these tokens
@@ -149,3 +192,17 @@ pub enum QueryMsg {
 pub struct MinterResponse {
     pub minter: String,
 }
+
+/// Royalty owed to `address` for a given sale, `address` is empty when no royalty is set
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoyaltiesInfoResponse {
+    pub address: String,
+    pub royalty_amount: Uint128,
+}
+
+/// Current user of a token, both fields are unset when it has none
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct UserOfResponse {
+    pub user: Option<String>,
+    pub expires: Option<Expiration>,
+}
@@ -1,7 +1,9 @@
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use cosmwasm_std::{to_binary, Addr, Binary, BlockInfo, Deps, Env, Order, StdError, StdResult};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, BlockInfo, Deps, Env, Order, StdError, StdResult, Uint128,
+};
 
 use cw721::{
     AllNftInfoResponse, ApprovalResponse, ApprovalsResponse, ContractInfoResponse, CustomMsg,
@@ -11,7 +13,7 @@ use cw721::{
 use cw_storage_plus::Bound;
 use cw_utils::maybe_addr;
 
-use crate::msg::{MinterResponse, QueryMsg};
+use crate::msg::{MinterResponse, QueryMsg, RoyaltiesInfoResponse, UserOfResponse};
 use crate::state::{Approval, Cw721Contract, TokenInfo};
 
 const DEFAULT_LIMIT: u32 = 10;
@@ -217,9 +219,52 @@ where
         })
     }
 
+    pub fn user_of(&self, deps: Deps, env: Env, token_id: String) -> StdResult<UserOfResponse> {
+        let token = self.tokens.load(deps.storage, &token_id)?;
+        Ok(match token.user.filter(|u| !u.is_expired(&env.block)) {
+            Some(user) => UserOfResponse {
+                user: Some(user.user.into_string()),
+                expires: Some(user.expires),
+            },
+            None => UserOfResponse {
+                user: None,
+                expires: None,
+            },
+        })
+    }
+
+    pub fn royalty_info(
+        &self,
+        deps: Deps,
+        token_id: String,
+        sale_price: Uint128,
+    ) -> StdResult<RoyaltiesInfoResponse> {
+        // make sure the token exists
+        self.tokens.load(deps.storage, &token_id)?;
+        let royalty = match self.token_royalties.may_load(deps.storage, &token_id)? {
+            Some(royalty) => Some(royalty),
+            None => self.royalty.may_load(deps.storage)?,
+        };
+        Ok(match royalty {
+            Some(royalty) => RoyaltiesInfoResponse {
+                address: royalty.payment_address.to_string(),
+                royalty_amount: sale_price.multiply_ratio(royalty.percentage, 100u64),
+            },
+            None => RoyaltiesInfoResponse {
+                address: String::new(),
+                royalty_amount: Uint128::zero(),
+            },
+        })
+    }
+
     pub fn query(&self, deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         match msg {
             QueryMsg::Minter {} => to_binary(&self.minter(deps)?),
+            QueryMsg::UserOf { token_id } => to_binary(&self.user_of(deps, env, token_id)?),
+            QueryMsg::RoyaltyInfo {
+                token_id,
+                sale_price,
+            } => to_binary(&self.royalty_info(deps, token_id, sale_price)?),
             QueryMsg::ContractInfo {} => to_binary(&self.contract_info(deps)?),
             QueryMsg::NftInfo { token_id } => to_binary(&self.nft_info(deps, token_id)?),
             QueryMsg::OwnerOf {
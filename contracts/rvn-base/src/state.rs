@@ -18,6 +18,10 @@ where
     /// Stored as (granter, operator) giving operator full control over granter's account
     pub operators: Map<'a, (&'a Addr, &'a Addr), Expiration>,
     pub tokens: IndexedMap<'a, &'a str, TokenInfo<T>, TokenIndexes<'a, T>>,
+    /// Royalty applied to every token of the collection
+    pub royalty: Item<'a, Royalty>,
+    /// Per token royalty, overriding the collection one
+    pub token_royalties: Map<'a, &'a str, Royalty>,
 
     pub(crate) _custom_response: PhantomData<C>,
 }
@@ -42,6 +46,8 @@ where
             "operators",
             "tokens",
             "tokens__owner",
+            "royalty",
+            "token_royalties",
         )
     }
 }
@@ -50,6 +56,7 @@ impl<'a, T, C> Cw721Contract<'a, T, C>
 where
     T: Serialize + DeserializeOwned + Clone,
 {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         contract_key: &'a str,
         minter_key: &'a str,
@@ -57,6 +64,8 @@ where
         operator_key: &'a str,
         tokens_key: &'a str,
         tokens_owner_key: &'a str,
+        royalty_key: &'a str,
+        token_royalties_key: &'a str,
     ) -> Self {
         let indexes = TokenIndexes {
             owner: MultiIndex::new(token_owner_idx, tokens_key, tokens_owner_key),
@@ -67,6 +76,8 @@ where
             token_count: Item::new(token_count_key),
             operators: Map::new(operator_key),
             tokens: IndexedMap::new(tokens_key, indexes),
+            royalty: Item::new(royalty_key),
+            token_royalties: Map::new(token_royalties_key),
             _custom_response: PhantomData,
         }
     }
@@ -102,6 +113,25 @@ pub struct TokenInfo<T> {
 
     /// You can add any custom metadata here when you extend cw721-base
     pub extension: T,
+
+    /// Account allowed to use the token until its expiration, in the style of ERC-4907.
+    /// It is kept when the token is transferred
+    #[serde(default)]
+    pub user: Option<UserInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct UserInfo {
+    /// Account using the token, it has no right to transfer it
+    pub user: Addr,
+    /// When the user role ends, it can't be changed or revoked before
+    pub expires: Expiration,
+}
+
+impl UserInfo {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        self.expires.is_expired(block)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -118,6 +148,14 @@ impl Approval {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Royalty {
+    /// Account receiving the royalties
+    pub payment_address: Addr,
+    /// Percentage of the sale price owed as royalty, between 0 and 100
+    pub percentage: u64,
+}
+
 pub struct TokenIndexes<'a, T>
 where
     T: Serialize + DeserializeOwned + Clone,